@@ -0,0 +1,54 @@
+// Timings the tests only check the mechanism behind (which threads do the hashing), since
+// wall-clock comparisons are too noisy to assert on. Run with `cargo bench`.
+use a4::block::Block;
+use std::time::{Duration, Instant};
+
+fn time(f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+// At difficulty 0 a search is one hash, so these times are all thread start-up
+fn cheap_blocks(n: u64) -> Vec<Block> {
+    (0..n).map(|i| Block { generation: i, ..Block::initial(0) }).collect()
+}
+
+// mine(1) runs on the calling thread instead of a one-worker queue
+fn one_worker() {
+    let cheap = cheap_blocks(200);
+    let direct = time(|| {
+        for b in &cheap {
+            b.clone().mine(1).unwrap();
+        }
+    });
+    let queued = time(|| {
+        for b in &cheap {
+            // mine_range_lowest always goes through a queue; 3 proofs is mine's first window at difficulty 0
+            b.mine_range_lowest(1, 0, 3, 1).unwrap();
+        }
+    });
+    println!("one worker, 200 blocks: {:?} on this thread, {:?} through a queue", direct, queued);
+}
+
+// mine_chain mines a run of blocks on one pool instead of starting a pool per block
+fn pooled_chain() {
+    let cheap = cheap_blocks(200);
+    let mut pooled = cheap.clone();
+    let pooled_time = time(|| Block::mine_chain(&mut pooled, 4).unwrap());
+    let mut separate = cheap;
+    let separate_time = time(|| {
+        for i in 0..separate.len() {
+            if i > 0 {
+                separate[i].prev_hash = separate[i - 1].hash();
+            }
+            separate[i].mine(4).unwrap();
+        }
+    });
+    println!("4 workers, 200 blocks: {:?} on one pool, {:?} with a pool each", pooled_time, separate_time);
+}
+
+fn main() {
+    one_worker();
+    pooled_chain();
+}
@@ -1,55 +1,436 @@
-use crate::queue::{Task, WorkQueue};
+use crate::queue::{par_map, Task, WorkQueue};
 use digest::consts::U32;
 use sha2::digest::generic_array::GenericArray;
 use sha2::{Digest, Sha256};
-use std::fmt::Write;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
 use std::sync;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 pub type Hash = GenericArray<u8, U32>;  // u means unsigned int
 
+// First 16 bytes of the hash read big-endian: a uniformly distributed key for bucketing blocks
+pub fn hash_high_u128(h: &Hash) -> u128 {
+    let mut high = [0u8; 16];
+    high.copy_from_slice(&h[..16]);
+    u128::from_be_bytes(high)
+}
+
+// Full hash as 64 lowercase hex chars, two per byte in order (same as the hash string uses)
+pub fn hash_to_hex(h: &Hash) -> String {
+    format!("{:x}", h)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseHashError {
+    WrongLength(usize),             // number of chars given, instead of 64
+    InvalidHex { index: usize },    // position of the first char that isn't a hex digit
+}
+
+impl fmt::Display for ParseHashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseHashError::WrongLength(n) => write!(f, "a hash is 64 hex chars, got {}", n),
+            ParseHashError::InvalidHex { index } => write!(f, "not a hex digit at position {}", index),
+        }
+    }
+}
+
+impl std::error::Error for ParseHashError {}
+
+// Inverse of hash_to_hex (upper-case digits are accepted too)
+pub fn parse_hash(s: &str) -> Result<Hash, ParseHashError> {
+    // count chars, not bytes, so the length reported is in the same unit as the one checked
+    let len = s.chars().count();
+    if len != 64 {
+        return Err(ParseHashError::WrongLength(len));
+    }
+    let mut h = Hash::default();
+    for (i, c) in s.chars().enumerate() {
+        let digit = c.to_digit(16).ok_or(ParseHashError::InvalidHex { index: i })? as u8;
+        h[i / 2] = (h[i / 2] << 4) | digit;
+    }
+    Ok(h)
+}
+
+// Anything a block can carry as its data: it's hashed through as_ref() as raw bytes
+pub trait Payload: AsRef<[u8]> + Clone + fmt::Debug + Send + Sync + 'static {}
+
+impl<T: AsRef<[u8]> + Clone + fmt::Debug + Send + Sync + 'static> Payload for T {}
+
 #[derive(Debug, Clone)]
-pub struct Block {
+pub struct Block<T = String> {
     pub prev_hash: Hash,      // hash of prev block
     pub generation: u64,      // index of current block (generation 0 has NO prev block)
     pub difficulty: u8,       // amount of work to add block to the chain
-    pub data: String,         // actual data in a block
+    pub data: T,              // actual data in a block
     pub proof: Option<u64>,   
+    pub coinbase: Option<Coinbase>,   // reward claimed by the miner, committed to by the proof
+    pub extra_nonce: u64,     // bumped to get fresh hashes for the same proofs once a search window is used up
+    pub timestamp: u64,       // Unix seconds when the block was created (0 = not recorded), committed to by the proof
+    pub hash_scheme: HashScheme,    // how the fields above and the proof are fed to the hash
+    pub difficulty_mode: DifficultyMode,    // which zero bits the difficulty counts
 }
 
+// Flat hashes the hash string as is. Its fields are joined with ':', so data (or a miner) with
+// colons in it can make two different blocks hash the same: data "x:1:m" with no coinbase and
+// data "x" with a 1 to "m" coinbase give the same string.
+// Separated hashes a domain tag, then H(header), H(data) and the proof as 8 little-endian
+// bytes. The header holds the fixed-size fields in binary and the miner as H(miner), so no
+// field can spill into another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashScheme {
+    Flat,
+    Separated,
+}
+
+// Which end of the hash the difficulty's zero bits have to be at. Trailing counts from the last
+// byte's lowest bit, Leading (as Bitcoin does) from the first byte's highest bit. Either way each
+// proof is valid with probability 2^-difficulty, but the proofs that are valid differ completely.
+// Not part of the hash: flipping it on a mined block just leaves it (almost surely) invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DifficultyMode {
+    #[default]
+    Trailing,
+    Leading,
+}
+
+impl DifficultyMode {
+    // Does `hash` have `difficulty` zero bits at this mode's end? A hash shorter than
+    // `difficulty` bits never does.
+    pub fn satisfied_by(self, difficulty: u8, hash: &[u8]) -> bool {
+        match self {
+            DifficultyMode::Trailing => Block::bytes_satisfy_difficulty(difficulty, hash),
+            DifficultyMode::Leading => {
+                if difficulty as usize > hash.len() * 8 {
+                    return false;
+                }
+                let n_bytes = (difficulty / 8) as usize;
+                let n_bits = difficulty % 8;
+                // the first n_bytes bytes, then the high n_bits bits of the byte after them
+                hash[..n_bytes].iter().all(|&byte| byte == 0)
+                    && (n_bits == 0 || hash[n_bytes] >> (8 - n_bits) == 0)
+            }
+        }
+    }
+}
+
+// The plain text blocks the rest of the crate (and Blockchain) work with
+pub type TextBlock = Block<String>;
+
+// Starts every Separated hash, so it can't collide with a hash of anything else
+const SEPARATED_HASH_TAG: &[u8] = b"cmpt383-block-v1";
+
+// e.g. "block 3 (difficulty 16, prev 0a0a0a0a..): Cool Data, proof 4321"
+// Data that isn't UTF-8 is shown lossily.
+impl<T: Payload> fmt::Display for Block<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "block {} (difficulty {}, prev {}..): {}, ",
+            self.generation,
+            self.difficulty,
+            &hash_to_hex(&self.prev_hash)[..8],
+            String::from_utf8_lossy(self.data.as_ref())
+        )?;
+        match self.proof {
+            Some(p) => write!(f, "proof {}", p),
+            None => write!(f, "<unmined>"),
+        }
+    }
+}
+
+// Current Unix time in seconds (0 if the clock is set before 1970)
+pub fn now_unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coinbase {
+    pub reward: u64,
+    pub miner: String,
+}
+
+// A proof as it's fed to the hash, on the stack: hashing one shouldn't allocate
+struct ProofBytes {
+    buf: [u8; 20],  // u64::MAX has 20 decimal digits
+    len: usize,
+}
+
+impl ProofBytes {
+    // ASCII decimal, the same bytes as proof.to_string()
+    fn decimal(mut proof: u64) -> ProofBytes {
+        let mut buf = [0u8; 20];
+        let mut start = buf.len();
+        loop {
+            start -= 1;
+            buf[start] = b'0' + (proof % 10) as u8;
+            proof /= 10;
+            if proof == 0 {
+                break;
+            }
+        }
+        buf.copy_within(start.., 0);
+        ProofBytes { buf, len: 20 - start }
+    }
+
+    fn little_endian(proof: u64) -> ProofBytes {
+        let mut buf = [0u8; 20];
+        buf[..8].copy_from_slice(&proof.to_le_bytes());
+        ProofBytes { buf, len: 8 }
+    }
+}
+
+impl AsRef<[u8]> for ProofBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+}
+
+// What a mining search came up with, and how many hashes it took across all workers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MiningResult {
+    pub proof: Option<u64>,     // None if nothing in the range was valid
+    pub hashes_checked: u64,
+    pub winning_chunk: Option<u64>,     // index of the chunk (0 = lowest) the proof came from
+}
+
+// How try_mine_for_proof searches, and when it gives up early
+#[derive(Debug, Clone, Default)]
+pub struct MiningConfig {
+    pub workers: usize,
+    pub range: Option<Range<u64>>,          // None => every u64 proof, a window at a time like mine_for_proof
+    pub cancel: Option<sync::Arc<AtomicBool>>,  // set it (from any thread) to stop with MineError::Cancelled
+    pub timeout: Option<Duration>,          // stop with MineError::Timeout once this much time has passed
+}
+
+impl MiningConfig {
+    pub fn new(workers: usize) -> MiningConfig {
+        MiningConfig { workers, ..MiningConfig::default() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MineError {
+    // no proof in [start, end) satisfies the difficulty: retry with a different/wider range
+    RangeExhausted { start: u64, end: u64 },
+    // the MiningConfig's cancel flag was set before a proof turned up
+    Cancelled,
+    // the MiningConfig's timeout ran out before a proof turned up
+    Timeout,
+    // mine_to_range was given lo > hi, so no hash can be inside it
+    EmptyHashRange,
+}
+
+impl fmt::Display for MineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MineError::RangeExhausted { start, end } => {
+                write!(f, "no valid proof in range [{}, {})", start, end)
+            }
+            MineError::Cancelled => write!(f, "mining was cancelled"),
+            MineError::Timeout => write!(f, "mining timed out"),
+            MineError::EmptyHashRange => write!(f, "empty hash range: lo is above hi"),
+        }
+    }
+}
+
+impl std::error::Error for MineError {}
+
+// Above this, DEFAULT_WINDOW_MULTIPLE * 2^difficulty (the size of one search window) overflows u64
+pub const MAX_DIFFICULTY: u8 = 62;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidDifficulty(pub u8);
+
+impl fmt::Display for InvalidDifficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "difficulty {} is above the supported maximum of {}", self.0, MAX_DIFFICULTY)
+    }
+}
+
+impl std::error::Error for InvalidDifficulty {}
+
+// Text block constructors, and the hash checks that don't care what data a block holds.
+// Block here means Block<String>, so Block::initial(..) / Block::hash_satisfies_difficulty(..)
+// need no type annotations.
 impl Block {
     pub fn initial(difficulty: u8) -> Block {
+        match Self::try_initial(difficulty) {
+            Ok(b) => b,
+            Err(e) => panic!("Block::initial: {}", e),
+        }
+    }
+
+    pub fn try_initial(difficulty: u8) -> Result<Block, InvalidDifficulty> {
+        Block::initial_with(difficulty, String::new())
+    }
+
+    // initial with a timestamp, e.g. now_unix_secs() for a genesis block that retarget can time from
+    pub fn initial_at(difficulty: u8, timestamp: u64) -> Block {
+        Block { timestamp, ..Self::initial(difficulty) }
+    }
+
+    pub fn hash_satisfies_difficulty(difficulty:u8,hash:Hash) -> bool {
+        // TODO: does the hash `hash` have `difficulty` trailing 0s
+        // (always trailing: blocks go through hash_meets_difficulty, which honours their DifficultyMode)
+        Self::bytes_satisfy_difficulty(difficulty, &hash)
+    }
+
+    // hash_satisfies_difficulty for a hash of any length; a hash shorter than
+    // `difficulty` bits can never satisfy it
+    pub fn bytes_satisfy_difficulty(difficulty: u8, hash: &[u8]) -> bool {
+        if difficulty == 0 {
+            return true;
+        }
+        if difficulty as usize > hash.len() * 8 {
+            return false;
+        }
+        
+        let n_bytes = (difficulty / 8) as usize;
+        let n_bits = difficulty % 8;
+
+        // check if last n_bytes ele satisfy
+        for i in 0..n_bytes {
+            if hash[hash.len() - 1 - i] != 0u8 {
+                return false;
+            }
+        }
+
+        // then the low n_bits bits of the byte just before them
+        if n_bits > 0 {
+            let mask = (1u8 << n_bits) - 1;
+            if hash[hash.len() - 1 - n_bytes] & mask != 0 {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl<T: Payload> Block<T> {
+    // try_initial, for any kind of data. Like initial and next, the timestamp is 0, which leaves it
+    // out of the hash string: use initial_at / next_at / next_now for a timestamped block.
+    pub fn initial_with(difficulty: u8, data: T) -> Result<Block<T>, InvalidDifficulty> {
+        if difficulty > MAX_DIFFICULTY {
+            return Err(InvalidDifficulty(difficulty));
+        }
         // TODO: create and return a new initial block
-        Block {
+        Ok(Block {
             prev_hash: Hash::default(),
             generation: 0,
-            difficulty: difficulty,
-            data: ("").to_string(),    // cannot write data: "" b/c required type is String but "" is &str (string literal)
-            proof: None
+            difficulty,
+            data,
+            proof: None,
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
+        })
+    }
+
+    pub fn next(previous: &Block<T>, data: T) -> Block<T> {
+        Self::next_at(previous, data, 0)
+    }
+
+    // next, stamped with the current time
+    pub fn next_now(previous: &Block<T>, data: T) -> Block<T> {
+        Self::next_at(previous, data, now_unix_secs())
+    }
+
+    // next at another difficulty, e.g. one from chain::retarget, instead of previous's
+    pub fn next_with_difficulty(previous: &Block<T>, data: T, difficulty: u8) -> Result<Block<T>, InvalidDifficulty> {
+        if difficulty > MAX_DIFFICULTY {
+            return Err(InvalidDifficulty(difficulty));
         }
+        Ok(Block { difficulty, ..Self::next(previous, data) })
     }
 
-    pub fn next(previous: &Block, data: String) -> Block {
+    // next with the given timestamp instead of 0
+    pub fn next_at(previous: &Block<T>, data: T, timestamp: u64) -> Block<T> {
         // TODO: create and return a block that could follow `previous` in the chain
         Block {
             prev_hash: previous.hash(),     // get prev block's hash
             generation: previous.generation + 1,
             difficulty: previous.difficulty,
-            data: data,
-            proof: None
+            data,
+            proof: None,
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp,
+            hash_scheme: previous.hash_scheme,
+            difficulty_mode: previous.difficulty_mode,
         }
     }
 
+    // Compare everything that goes into the hash except the proof,
+    // i.e. would these two blocks be mining the same thing? difficulty_mode isn't hashed, so two
+    // blocks that differ only in it have the same content (and the same hash for every proof).
+    pub fn same_content(&self, other: &Block<T>) -> bool {
+        self.prev_hash == other.prev_hash
+            && self.generation == other.generation
+            && self.difficulty == other.difficulty
+            && self.data.as_ref() == other.data.as_ref()
+            && self.coinbase == other.coinbase
+            && self.extra_nonce == other.extra_nonce
+            && self.timestamp == other.timestamp
+            && self.hash_scheme == other.hash_scheme
+    }
+
+    // What HashScheme::Flat hashes (with <data> as the data's raw bytes).
+    // Layout: "<prev_hash hex>:<generation>:<difficulty>:<data>[:<reward>:<miner>][:x<extra_nonce>][:t<timestamp>]:<proof>"
+    // The bracketed parts are left out when unset (no coinbase / 0), so blocks mined before they
+    // existed keep their hashes. Changing this format invalidates every block mined so far.
+    // <prev_hash hex> is hash_to_hex: GenericArray's LowerHex always writes each byte as two
+    // zero-padded digits (the width in the old "{:02x}" was ignored), so 64 chars in total.
     pub fn hash_string_for_proof(&self, proof: u64) -> String {
         // TODO: return the hash string this block would have if we set the proof to `proof`.
         // self.set_proof(proof);  // borrowing self as immmut => cannot mutate
-        format!(
-            "{:02x}:{}:{}:{}:{}",
-            self.prev_hash,        // Previous hash in hex format
+        format!("{}{}", self.hash_string_prefix(), proof)
+    }
+
+    // Everything in the hash string before the proof, up to and including the last ':'.
+    // Data that isn't UTF-8 is shown lossily here; the hash itself uses the exact bytes.
+    fn hash_string_prefix(&self) -> String {
+        let (head, tail) = self.flat_head_tail();
+        format!("{}{}{}", head, String::from_utf8_lossy(self.data.as_ref()), tail)
+    }
+
+    // The flat hash string around the data: (up to the data, from after the data to the proof)
+    fn flat_head_tail(&self) -> (String, String) {
+        // blocks without a coinbase / extra nonce / timestamp keep the original layout, so their hashes don't change
+        let coinbase = match &self.coinbase {
+            Some(cb) => format!(":{}:{}", cb.reward, cb.miner),
+            None => String::new(),
+        };
+        let extra_nonce = match self.extra_nonce {
+            0 => String::new(),
+            n => format!(":x{}", n),
+        };
+        let timestamp = match self.timestamp {
+            0 => String::new(),
+            t => format!(":t{}", t),
+        };
+        let head = format!(
+            "{}:{}:{}:",
+            hash_to_hex(&self.prev_hash),   // Previous hash in hex format
             self.generation,
             self.difficulty,
-            self.data,             
-            proof                  // Provided proof
-        )
+        );
+        let tail = format!(
+            "{}{}{}:",
+            coinbase,              // ":reward:miner" or nothing
+            extra_nonce,           // ":x<n>" or nothing
+            timestamp,             // ":t<secs>" or nothing
+        );
+        (head, tail)
     }
 
     pub fn hash_string(&self) -> String {
@@ -58,180 +439,945 @@ impl Block {
         self.hash_string_for_proof(p)
     }
 
+    // Non-panicking hash_string: None if the block hasn't been mined
+    pub fn try_hash_string(&self) -> Option<String> {
+        self.proof.map(|p| self.hash_string_for_proof(p))
+    }
+
     pub fn hash_for_proof(&self, proof: u64) -> Hash {      // implicitly pass ref of this func calling obj as 1st param 
         // TODO: return the block's hash as it would be if we set the proof to `proof`.
-        let mut d = Sha256::new();
-        d.update(self.hash_string_for_proof(proof));    
-        /* 
-        must prefix w/ self. as hash_string_for_proof is a method of Block, otherwise Rust assumes this func is local func
-        defined in same scope of current method
-        */  
-        // dont have to pass self as param 
-        d.finalize()
+        self.hash_for_proof_with::<Sha256>(proof)
     }
 
-    pub fn hash(&self) -> Hash {
-        // self.proof.unwrap() panics if block not mined
-        let p = self.proof.unwrap();
-        self.hash_for_proof(p)
+    // hash_for_proof with any digest algorithm, e.g. Sha512; output size follows the algorithm
+    pub fn hash_for_proof_with<D: Digest>(&self, proof: u64) -> GenericArray<u8, D::OutputSize> {
+        let mut d = self.proof_midstate::<D>();
+        d.update(self.proof_bytes(proof));
+        d.finalize()
     }
 
-    pub fn set_proof(self: &mut Block, proof: u64) {
-        self.proof = Some(proof);
+    // A hasher that has been fed everything that comes before the proof
+    fn proof_midstate<D: Digest>(&self) -> D {
+        let mut d = D::new();
+        match self.hash_scheme {
+            HashScheme::Flat => {
+                // the hash string's bytes, with the data's own bytes in the middle
+                let (head, tail) = self.flat_head_tail();
+                d.update(head);
+                d.update(self.data.as_ref());
+                d.update(tail);
+            }
+            HashScheme::Separated => {
+                d.update(SEPARATED_HASH_TAG);
+                d.update(self.separated_header::<D>());
+                d.update(D::digest(self.data.as_ref()));
+            }
+        }
+        d
     }
 
-    pub fn hash_satisfies_difficulty(difficulty:u8,hash:Hash) -> bool {
-        // TODO: does the hash `hash` have `difficulty` trailing 0s
-        if difficulty == 0 {
-            return true;
+    // How the proof itself is fed to the hash
+    fn proof_bytes(&self, proof: u64) -> ProofBytes {
+        match self.hash_scheme {
+            HashScheme::Flat => ProofBytes::decimal(proof),
+            HashScheme::Separated => ProofBytes::little_endian(proof),
         }
-        
-        let n_bytes = difficulty / 8;
-        let n_bits = difficulty % 8;
+    }
 
-        // check if last n_bytes ele satisfy
-        for i in 0..n_bytes {
-            if hash[hash.len() - 1 - i as usize] != 0u8 {
-                return false;
-            }
+    // hash_for_proof for many proofs of the same block: everything before the proof is hashed
+    // once here, and each call only clones that state and adds the proof
+    fn proof_hasher(&self) -> impl Fn(u64) -> Hash + '_ {
+        let midstate = self.proof_midstate::<Sha256>();
+        move |proof| {
+            let mut d = midstate.clone();
+            d.update(self.proof_bytes(proof));
+            d.finalize()
         }
+    }
 
-        for i in 0..n_bits {
-            if (1 << n_bits) & (hash[hash.len() - 1 - i as usize]) != 0 {
-                return false;
+    // H(prev_hash, generation, difficulty, coinbase, extra_nonce, timestamp) for HashScheme::Separated.
+    // Integers are little-endian; the coinbase is a 0/1 tag, then the reward and H(miner).
+    fn separated_header<D: Digest>(&self) -> GenericArray<u8, D::OutputSize> {
+        let mut d = D::new();
+        d.update(self.prev_hash);
+        d.update(self.generation.to_le_bytes());
+        d.update([self.difficulty]);
+        match &self.coinbase {
+            Some(cb) => {
+                d.update([1u8]);
+                d.update(cb.reward.to_le_bytes());
+                d.update(D::digest(cb.miner.as_bytes()));
             }
+            None => d.update([0u8]),
         }
+        d.update(self.extra_nonce.to_le_bytes());
+        d.update(self.timestamp.to_le_bytes());
+        d.finalize()
+    }
+
+    pub fn hash(&self) -> Hash {
+        // self.proof.unwrap() panics if block not mined
+        let p = self.proof.unwrap();
+        self.hash_for_proof(p)
+    }
 
+    // Non-panicking hash: None if the block hasn't been mined
+    pub fn try_hash(&self) -> Option<Hash> {
+        self.proof.map(|p| self.hash_for_proof(p))
+    }
 
-        // DIFF APPROACH
-        // let n_bytes = (difficulty / 8) as usize;
-        // let n_bits = difficulty % 8;
+    pub fn set_proof(self: &mut Block<T>, proof: u64) {
+        self.proof = Some(proof);
+    }
 
-        // // check if last n_bytes ele satisfy
-        // for i in 0..n_bytes {
-        //     if hash[hash.len() - 1 - i] != 0u8 {
-        //         return false;
-        //     }
-        // }
+    // The coinbase is part of the hash, so any existing proof is no longer meaningful.
+    pub fn set_coinbase(self: &mut Block<T>, reward: u64, miner: String) {
+        self.coinbase = Some(Coinbase { reward, miner });
+        self.proof = None;
+    }
 
-        // if n_bits > 0 {
-        //     let n = (hash[31 - n_bytes]) as usize;
-        //     if n % (1 << n_bits) != 0 {
-        //         return false;
-        //     }           
-        // }
+    // Same for the data: the block has to be mined again before it's valid. Assigning to `data`
+    // directly keeps the old proof, which no longer means anything.
+    pub fn set_data(self: &mut Block<T>, data: T) {
+        self.data = data;
+        self.proof = None;
+    }
 
-        true
+    // Does `hash` meet this block's difficulty, counted the way its difficulty_mode says?
+    pub fn hash_meets_difficulty(&self, hash: &[u8]) -> bool {
+        self.difficulty_mode.satisfied_by(self.difficulty, hash)
+    }
+
+    // hash_meets_difficulty for this block's own hash; false if unmined
+    pub fn meets_difficulty(&self) -> bool {
+        self.try_hash().is_some_and(|h| self.hash_meets_difficulty(&h))
     }
 
     pub fn is_valid_for_proof(&self, proof: u64) -> bool {
-        Self::hash_satisfies_difficulty(self.difficulty,self.hash_for_proof(proof))
+        self.hash_meets_difficulty(&self.hash_for_proof(proof))
+    }
+
+    // Index of the first proof whose hash doesn't meet `share_difficulty`, None if they all do.
+    // The proof is hashed last, so the hasher state after everything before it (the midstate)
+    // is computed once and cloned for each proof.
+    pub fn first_invalid_proof(&self, proofs: &[u64], share_difficulty: u8) -> Option<usize> {
+        let hash_for = self.proof_hasher();
+        proofs
+            .iter()
+            .position(|&proof| !self.difficulty_mode.satisfied_by(share_difficulty, &hash_for(proof)))
+    }
+
+    pub fn is_valid_for_proof_with<D: Digest>(&self, proof: u64) -> bool {
+        self.hash_meets_difficulty(&self.hash_for_proof_with::<D>(proof))
     }
 
     pub fn is_valid(&self) -> bool {
         if self.proof.is_none() {
             return false;
         }
+        // every hash satisfies difficulty 0 => skip hashing
+        if self.difficulty == 0 {
+            return true;
+        }
         self.is_valid_for_proof(self.proof.unwrap())
     }
 
+    // Could this block come straight after `previous`? It points at previous's hash with the next
+    // generation number, keeps (or raises) its difficulty, and has a valid proof itself.
+    // The one-block version of Blockchain::verify(0); false if `previous` is unmined.
+    pub fn verify_link(&self, previous: &Block<T>) -> bool {
+        previous.try_hash() == Some(self.prev_hash)
+            && self.generation == previous.generation + 1
+            && self.difficulty >= previous.difficulty
+            && self.is_valid()
+    }
+
     // Mine in a very simple way: check sequentially until a valid hash is found.
     // This doesn't *need* to be used in any way, but could be used to do some mining
     // before your .mine is complete. Results should be the same as .mine (but slower).
-    pub fn mine_serial(self: &mut Block) {
+    pub fn mine_serial(self: &mut Block<T>) {
+        let p = {
+            let hash_for = self.proof_hasher();
+            (0u64..).find(|&p| self.hash_meets_difficulty(&hash_for(p))).unwrap()
+        };
+        self.proof = Some(p);
+    }
+
+    // mine_serial with another digest algorithm. The proof is only valid under `D`,
+    // so check it with is_valid_for_proof_with::<D>, not is_valid.
+    pub fn mine_serial_with<D: Digest>(self: &mut Block<T>) {
         let mut p = 0u64;
-        while !self.is_valid_for_proof(p) {
+        while !self.is_valid_for_proof_with::<D>(p) {
             p += 1;
         }
         self.proof = Some(p);
     }
 
-    pub fn mine_range(self: &Block, workers: usize, start: u64, end: u64, chunks: u64) -> u64 {
+    pub fn mine_range(self: &Block<T>, workers: usize, start: u64, end: u64, chunks: u64) -> u64 {
         // TODO: with `workers` threads, check proof values in the given range, breaking up
 	    // into `chunks` tasks in a work queue. Return the first valid proof found.
         // HINTS:
         // - Create and use a queue::WorkQueue.
         // - Use sync::Arc to wrap a clone of self for sharing.
-            
+        self.mine_range_counted(workers, start, end, chunks).proof.unwrap_or(end)
+    }
+
+    // mine_range, but also says which chunk (0 = the one at `start`) the proof came from:
+    // Some((proof, chunk)), or None if the range has no valid proof
+    pub fn mine_range_winner(
+        self: &Block<T>,
+        workers: usize,
+        start: u64,
+        end: u64,
+        chunks: u64,
+    ) -> Option<(u64, u64)> {
+        let r = self.mine_range_counted(workers, start, end, chunks);
+        r.proof.zip(r.winning_chunk)
+    }
+
+    // mine_range, plus the number of proofs the workers hashed, for hashes-per-second figures
+    pub fn mine_range_counted(
+        self: &Block<T>,
+        workers: usize,
+        start: u64,
+        end: u64,
+        chunks: u64,
+    ) -> MiningResult {
+        self.search_range(workers, start, end, chunks, MiningTarget::Difficulty, &|_| {})
+    }
+
+    // mine_range, calling `on_progress` with the number of chunks finished so far each time
+    // another one comes back without a proof. It's called on this thread, so it needn't be Sync.
+    pub fn mine_range_with_progress(
+        self: &Block<T>,
+        workers: usize,
+        start: u64,
+        end: u64,
+        chunks: u64,
+        on_progress: impl Fn(u64),
+    ) -> u64 {
+        self.search_range(workers, start, end, chunks, MiningTarget::Difficulty, &on_progress)
+            .proof
+            .unwrap_or(end)
+    }
+
+    // Split [start, end) into (at most) `chunks` MiningTasks
+    fn chunk_tasks(
+        self: &Block<T>,
+        start: u64,
+        end: u64,
+        chunks: u64,
+        target: MiningTarget,
+    ) -> Vec<MiningTask<T>> {
+        // Create an Arc<Block> for sharing across threads
+        let block = sync::Arc::new(self.clone());
+        chunk_ranges(start, end, chunks)
+            .zip(0..)
+            .map(|(r, chunk_id)| MiningTask {
+                block: block.clone(),
+                chunk_id,
+                start: r.start,
+                end: r.end,
+                target: target.clone(),
+                found: None,
+                best: None,
+            })
+            .collect()
+    }
+
+    // mine_range_counted, but for any MiningTarget: first proof in [start, end) hitting it
+    fn search_range(
+        self: &Block<T>,
+        workers: usize,
+        start: u64,
+        end: u64,
+        chunks: u64,
+        target: MiningTarget,
+        on_progress: &dyn Fn(u64),
+    ) -> MiningResult {
+        let mut result = MiningResult { proof: None, hashes_checked: 0, winning_chunk: None };
+        let mut chunks_done = 0;
+        if start >= end {
+            return result;
+        }
+
+        // a single worker gains nothing from a queue: scan the chunks in order on this thread,
+        // which finds the same (lowest) proof the one worker would, minus the thread startup.
+        // No workers would mean nobody mines and recv panics: treat it as one.
+        if workers <= 1 {
+            for task in self.chunk_tasks(start, end, chunks, target) {
+                let r = task.run().expect("MiningTask always reports");
+                result.hashes_checked += r.hashes_checked;
+                if r.proof.is_some() {
+                    result.proof = r.proof;
+                    result.winning_chunk = r.winning_chunk;
+                    break;
+                }
+                chunks_done += 1;
+                on_progress(chunks_done);
+            }
+            return result;
+        }
+
         // Create a work queue with the specified number of workers
         let mut queue = WorkQueue::new(workers);
                 
-        // Create an Arc<Block> for sharing across threads
+        let tasks_submitted = queue
+            .enqueue_all(self.chunk_tasks(start, end, chunks, target))
+            .expect("a fresh queue accepts tasks");
+
+        // Every chunk that runs reports, hit or not, so waiting for one output per chunk can't
+        // hang when the range has no valid proof. One that panics doesn't: iter_n stops once
+        // every chunk has settled, and the panic is re-raised below.
+        for r in queue.iter_n(tasks_submitted) {
+            result.hashes_checked += r.hashes_checked;
+            if r.proof.is_some() {
+                result.proof = r.proof;
+                result.winning_chunk = r.winning_chunk;
+                break;
+            }
+            chunks_done += 1;
+            on_progress(chunks_done);
+        }
+        // As soon as one proof arrives, cancel the queue so the other workers stop scanning
+        // within CANCEL_CHECK_INTERVAL proofs and chunks still queued are skipped. The flag
+        // belongs to this call's own queue, so a cancelled search can't affect the next one.
+        // Chunks cut short still report what they hashed; those outputs are left in the
+        // channel after shutdown.
+        queue.cancel();
+        queue.shutdown();
+        while let Ok(r) = queue.try_recv() {
+            result.hashes_checked += r.hashes_checked;
+        }
+        reraise_task_panic(&queue);
+        result
+    }
+
+    // The lowest valid proof in [start, end), same as mine_serial would find, or None.
+    // mine_range returns as soon as any chunk hits, which might not be the lowest chunk. This
+    // waits for every chunk to finish instead, so it's slower: the whole range gets scanned
+    // up to the first hit of each chunk, even when an early chunk hits right away.
+    pub fn mine_range_lowest(self: &Block<T>, workers: usize, start: u64, end: u64, chunks: u64) -> Option<u64> {
+        // no workers would mean nobody mines and this waits forever: use at least one
+        let workers = workers.max(1);
+        let mut queue = WorkQueue::new(workers);
+        let tasks_submitted = queue
+            .enqueue_all(self.chunk_tasks(start, end, chunks, MiningTarget::Difficulty))
+            .expect("a fresh queue accepts tasks");
+        // each chunk reports the first hit in its own range, i.e. its lowest
+        collect_reraising(&mut queue, tasks_submitted).iter().filter_map(|r| r.proof).min()
+    }
+
+    // mine_range_lowest without scanning past a proof that's already been found: the chunks share
+    // the lowest hit so far, and each stops once it's scanning above it (checked every
+    // CANCEL_CHECK_INTERVAL proofs), so chunks starting above it barely run at all.
+    pub fn mine_range_coordinated(self: &Block<T>, workers: usize, start: u64, end: u64, chunks: u64) -> Option<u64> {
+        let best = sync::Arc::new(AtomicU64::new(u64::MAX));
+        let mut tasks = self.chunk_tasks(start, end, chunks, MiningTarget::Difficulty);
+        for task in &mut tasks {
+            task.best = Some(best.clone());
+        }
+        // no workers would mean nobody mines and this waits forever: use at least one
+        let workers = workers.max(1);
+        let mut queue = WorkQueue::new(workers);
+        let tasks_submitted = queue.enqueue_all(tasks).expect("a fresh queue accepts tasks");
+        collect_reraising(&mut queue, tasks_submitted).iter().filter_map(|r| r.proof).min()
+    }
+
+    // Every valid proof in [start, end), in order, not just the first: for checking that about
+    // 1 in 2^difficulty proofs are valid. Each chunk collects all of its own hits.
+    pub fn valid_proofs_in(self: &Block<T>, start: u64, end: u64, workers: usize) -> Vec<u64> {
+        // no workers would mean nobody hashes and this waits forever: use at least one
+        let workers = workers.max(1);
         let block = sync::Arc::new(self.clone());
+        let chunks = chunk_ranges(start, end, default_chunks(workers, end.saturating_sub(start)));
+        // par_map keeps the chunks in order, and each chunk's hits are in order
+        par_map(workers, chunks, move |r: Range<u64>| {
+            let hash_for = block.proof_hasher();
+            Some(r.filter(|&p| block.hash_meets_difficulty(&hash_for(p))).collect::<Vec<u64>>())
+        })
+        .concat()
+    }
 
-        // Calculate the size of each chunk
-        let chunk_size = ((end - start) + chunks - 1) / chunks;
-
-        // Create and submit tasks for each chunk
-        for chunk_idx in 0..chunks {
-            let chunk_start = start + chunk_idx * chunk_size;
-            let chunk_end = if chunk_idx == chunks - 1 {
-                // Make sure the last chunk includes any remaining values
-                end
-            } else {
-                chunk_start.min(end).saturating_add(chunk_size).min(end)
-            };
+    // mine_range with a wall-clock limit: None if no proof turned up within `timeout` (or the
+    // range has none). Either way the workers are cancelled and joined before it returns, so
+    // a timed-out search stops within CANCEL_CHECK_INTERVAL proofs per worker.
+    pub fn mine_range_timeout(
+        self: &Block<T>,
+        workers: usize,
+        start: u64,
+        end: u64,
+        chunks: u64,
+        timeout: Duration,
+    ) -> Option<u64> {
+        let deadline = Instant::now() + timeout;
+        // no workers would mean nobody mines and this waits forever: use at least one
+        let workers = workers.max(1);
+        let mut queue = WorkQueue::new(workers);
+        let tasks_submitted = queue
+            .enqueue_all(self.chunk_tasks(start, end, chunks, MiningTarget::Difficulty))
+            .expect("a fresh queue accepts tasks");
 
-            // Not create empty chunks
-            if chunk_start >= end || chunk_start >= chunk_end {
-                continue;
+        let mut proof = None;
+        for _ in 0..tasks_submitted {
+            match queue.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(r) if r.proof.is_some() => {
+                    proof = r.proof;
+                    break;
+                }
+                Ok(_) => (),
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
             }
-            
-            // Create a new mining task for this chunk
-            let task = MiningTask {
-                block: block.clone(),
-                start: chunk_start,
-                end: chunk_end,
-            };
-            
-            // Add the task to the work queue
-            let _ = queue.enqueue(task);
         }
+        queue.cancel();
+        queue.shutdown();
+        proof
+    }
 
-        let tasks_submitted = chunks.min((end - start + chunk_size - 1) / chunk_size);
+    // mine_range, but says so when nothing in [start, end) is valid instead of handing back end
+    pub fn try_mine_range(
+        self: &Block<T>,
+        workers: usize,
+        start: u64,
+        end: u64,
+        chunks: u64,
+    ) -> Result<u64, MineError> {
+        let proof = self.mine_range(workers, start, end, chunks);
+        if proof < end {
+            Ok(proof)
+        } else {
+            Err(MineError::RangeExhausted { start, end })
+        }
+    }
 
-        for _ in 0..tasks_submitted {
-            let proof = queue.recv();
-            if proof < end {  // Valid proof found
-                return proof;
+    pub fn mine_for_proof(self: &Block<T>, workers: usize) -> Result<u64, MineError> {
+        self.mine_for_proof_window(workers, DEFAULT_WINDOW_MULTIPLE)
+    }
+
+    // Search windows of `multiple` * 2^difficulty proofs, moving on to the next window
+    // whenever one comes up empty. Only fails once the whole u64 proof space is used up.
+    // Each window is split into default_chunks chunks.
+    pub fn mine_for_proof_window(self: &Block<T>, workers: usize, multiple: u64) -> Result<u64, MineError> {
+        self.mine_windows(workers, multiple, None)
+    }
+
+    // mine_for_proof with every window split into exactly `chunks` chunks
+    pub fn mine_for_proof_tuned(self: &Block<T>, workers: usize, chunks: u64) -> Result<u64, MineError> {
+        self.mine_windows(workers, DEFAULT_WINDOW_MULTIPLE, Some(chunks))
+    }
+
+    // None => default_chunks for each window's size
+    fn mine_windows(self: &Block<T>, workers: usize, multiple: u64, chunks: Option<u64>) -> Result<u64, MineError> {
+        Self::search_windows(self.window_size(multiple), |range_start, range_end| {
+            let chunks = chunks.unwrap_or_else(|| default_chunks(workers, range_end - range_start));
+            Ok(self.try_mine_range(workers, range_start, range_end, chunks).ok())
+        })
+    }
+
+    // mine_for_proof that can be stopped: searches cfg.range (or every proof, a window at a time)
+    // and gives up with Cancelled once cfg.cancel is set, or Timeout once cfg.timeout has passed.
+    // Both are checked every STOP_POLL while the workers run, and the workers are cancelled and
+    // joined before it returns.
+    pub fn try_mine_for_proof(self: &Block<T>, cfg: &MiningConfig) -> Result<u64, MineError> {
+        let deadline = cfg.timeout.map(|t| Instant::now() + t);
+        let cancel = cfg.cancel.as_deref();
+        match &cfg.range {
+            Some(r) => self
+                .search_stoppable(cfg.workers, r.start, r.end, cancel, deadline)?
+                .ok_or(MineError::RangeExhausted { start: r.start, end: r.end }),
+            None => Self::search_windows(self.window_size(DEFAULT_WINDOW_MULTIPLE), |range_start, range_end| {
+                self.search_stoppable(cfg.workers, range_start, range_end, cancel, deadline)
+            }),
+        }
+    }
+
+    // First valid proof in [start, end) (Ok(None) if there is none), or Cancelled/Timeout
+    fn search_stoppable(
+        self: &Block<T>,
+        workers: usize,
+        start: u64,
+        end: u64,
+        cancel: Option<&AtomicBool>,
+        deadline: Option<Instant>,
+    ) -> Result<Option<u64>, MineError> {
+        // no workers would mean nobody mines and this waits forever: use at least one
+        let workers = workers.max(1);
+        let mut queue = WorkQueue::new(workers);
+        let chunks = default_chunks(workers, end.saturating_sub(start));
+        let tasks_submitted = queue
+            .enqueue_all(self.chunk_tasks(start, end, chunks, MiningTarget::Difficulty))
+            .expect("a fresh queue accepts tasks");
+
+        let mut reported = 0;
+        let outcome = loop {
+            if reported == tasks_submitted {
+                break Ok(None);
+            }
+            if queue.panicked_tasks() > 0 {
+                queue.cancel();
+                queue.shutdown();
+                reraise_task_panic(&queue);
+            }
+            if cancel.is_some_and(|c| c.load(Ordering::Relaxed)) {
+                break Err(MineError::Cancelled);
+            }
+            let mut wait = STOP_POLL;
+            if let Some(d) = deadline {
+                let left = d.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    break Err(MineError::Timeout);
+                }
+                wait = wait.min(left);
+            }
+            match queue.recv_timeout(wait) {
+                Ok(r) if r.proof.is_some() => break Ok(r.proof),
+                Ok(_) => reported += 1,
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break Ok(None),
+            }
+        };
+        queue.cancel();
+        queue.shutdown();
+        outcome
+    }
+
+    // multiple * 2^(bits that must be zero), saturating: never overflows, whatever the difficulty.
+    // A multiple of 0 counts as 1, since an empty window would never move search_windows along.
+    pub(crate) fn window_size(self: &Block<T>, multiple: u64) -> u64 {
+        multiple.max(1).saturating_mul(1u64.checked_shl(self.difficulty as u32).unwrap_or(u64::MAX))
+    }
+
+    // Call `search` on [0, window), [window, 2 * window), ... until it finds something (or fails)
+    fn search_windows(
+        window: u64,
+        mut search: impl FnMut(u64, u64) -> Result<Option<u64>, MineError>,
+    ) -> Result<u64, MineError> {
+        let mut range_start: u64 = 0;
+        loop {
+            let range_end = range_start.saturating_add(window);
+            if let Some(proof) = search(range_start, range_end)? {
+                return Ok(proof);
+            }
+            if range_end == u64::MAX {
+                return Err(MineError::RangeExhausted { start: 0, end: u64::MAX });
+            }
+            range_start = range_end;
+        }
+    }
+
+    // Only sets the proof if a valid one was found, so the block is never left with a bogus proof
+    pub fn mine(self: &mut Block<T>, workers: usize) -> Result<(), MineError> {
+        self.proof = Some(self.mine_for_proof(workers)?);
+        Ok(())
+    }
+
+    // Mine a run of blocks in order on one worker pool instead of starting a pool per block.
+    // Each block is re-pointed at the hash of the one before it once that one is mined, so the
+    // result is a properly linked sequence. Stops at the first block that can't be mined.
+    pub fn mine_chain(blocks: &mut [Block<T>], workers: usize) -> Result<(), MineError> {
+        // no workers would mean nobody mines and this waits forever: use at least one
+        let workers = workers.max(1);
+        let mut queue = WorkQueue::new(workers);
+        for i in 0..blocks.len() {
+            if i > 0 {
+                blocks[i].prev_hash = blocks[i - 1].hash();
+            }
+            let b = &blocks[i];
+            let proof = Self::search_windows(b.window_size(DEFAULT_WINDOW_MULTIPLE), |range_start, range_end| {
+                let chunks = default_chunks(workers, range_end - range_start);
+                Ok(b.search_pooled(&mut queue, range_start, range_end, chunks))
+            })?;
+            blocks[i].proof = Some(proof);
+        }
+        Ok(())
+    }
+
+    // First valid proof in [start, end) using an existing pool. Can't cancel the queue (that
+    // would stick for the next search), so the chunks share a found flag instead; every chunk
+    // still reports, leaving the queue idle for the next call.
+    fn search_pooled(self: &Block<T>, queue: &mut WorkQueue<MiningTask<T>>, start: u64, end: u64, chunks: u64) -> Option<u64> {
+        let found = sync::Arc::new(AtomicBool::new(false));
+        let mut tasks = self.chunk_tasks(start, end, chunks, MiningTarget::Difficulty);
+        for t in &mut tasks {
+            t.found = Some(found.clone());
+        }
+        let tasks_submitted = queue.enqueue_all(tasks).expect("the pool accepts tasks until we drop it");
+        collect_reraising(queue, tasks_submitted).iter().find_map(|r| r.proof)
+    }
+
+    // Work-stealing alternative to mine_range: no chunk tuning, workers keep pulling batches of
+    // STEAL_BATCH proofs off a shared counter until someone finds a proof. Gives the lowest
+    // valid proof in [start, end) (same as mine_serial would), or end if there is none.
+    pub fn mine_range_shared(self: &Block<T>, workers: usize, start: u64, end: u64) -> u64 {
+        self.steal_range(workers, start, end).0
+    }
+
+    // mine_range_shared, plus how many batches each worker ended up scanning
+    pub(crate) fn steal_range(self: &Block<T>, workers: usize, start: u64, end: u64) -> (u64, Vec<u64>) {
+        // no workers would mean nobody mines and this waits forever: use at least one
+        let workers = workers.max(1);
+        let mut queue = WorkQueue::new(workers);
+        let shared = sync::Arc::new(StealState {
+            block: self.clone(),
+            next: AtomicU64::new(start),
+            end,
+            found: AtomicBool::new(false),
+        });
+        let _ = queue.enqueue_all((0..workers).map(|_| StealingTask { shared: shared.clone() }));
+
+        // Batches are handed out in increasing order and a batch, once taken, is scanned up to
+        // its first hit: every batch below the winning one was taken earlier and also finishes.
+        // So the smallest reported proof is the smallest in the whole range.
+        let mut best = end;
+        let mut batches = Vec::with_capacity(workers);
+        for (proof, n) in collect_reraising(&mut queue, workers) {
+            best = proof.map_or(best, |p| best.min(p));
+            batches.push(n);
+        }
+        (best, batches)
+    }
+
+    // Only ever try proofs in [0, window): when that comes up empty, bump extra_nonce (which
+    // changes every hash) and try the same proofs again. The search space is then unlimited
+    // without the proof itself ever growing past `window`.
+    pub fn mine_with_extra_nonce(self: &mut Block<T>, workers: usize, window: u64) {
+        // with no proofs to try this would bump extra_nonce forever (mine_range treats 0 workers as 1)
+        assert!(window > 0, "mine_with_extra_nonce needs a window");
+        let chunks = default_chunks(workers, window);
+        loop {
+            let proof = self.mine_range(workers, 0, window, chunks);
+            if proof < window {
+                self.proof = Some(proof);
+                return;
             }
+            self.extra_nonce += 1;
         }
-        // If no valid proof was found, return the end value
-        end
+    }
+
+    // Mine several independent blocks on one shared pool instead of a pool per block.
+    // Sets each block's proof and returns them keyed by generation. Like mine_for_proof, a block
+    // gets every u64 proof tried and no more: if none is valid it's left unmined and isn't in the map.
+    pub fn par_mine_blocks(blocks: &mut [Block<T>], workers: usize) -> HashMap<u64, u64> {
+        // no workers would mean nobody mines and this waits forever: use at least one
+        let workers = workers.max(1);
+        let mut queue = WorkQueue::new(workers);
+        let mut proofs = HashMap::new();
+
+        // each round, every unmined block searches its next window; the ones that miss go again,
+        // until they reach u64::MAX (the end search_windows stops at too)
+        let mut range_starts = vec![0u64; blocks.len()];
+        let searching = |b: &Block<T>, range_start: u64| b.proof.is_none() && range_start < u64::MAX;
+        while blocks.iter().zip(&range_starts).any(|(b, &range_start)| searching(b, range_start)) {
+            let mut tasks = Vec::new();
+            for (index, b) in blocks.iter().enumerate() {
+                let range_start = range_starts[index];
+                if !searching(b, range_start) {
+                    continue;
+                }
+                let range_end = range_start.saturating_add(b.window_size(DEFAULT_WINDOW_MULTIPLE));
+                range_starts[index] = range_end;
+                let chunks = default_chunks(workers, range_end - range_start);
 
+                // as in mine_range_coordinated: a block's chunks stop once they're past its lowest hit
+                let best = sync::Arc::new(AtomicU64::new(u64::MAX));
+                let chunk_tasks = b.chunk_tasks(range_start, range_end, chunks, MiningTarget::Difficulty);
+                tasks.extend(chunk_tasks.into_iter().map(|mut task| {
+                    task.best = Some(best.clone());
+                    TaggedMiningTask { index, task }
+                }));
+            }
+
+            // every tagged task reports, hit or not, so we know when the round is done;
+            // keep the lowest hit per block
+            let tasks_submitted = queue.enqueue_all(tasks).expect("the pool accepts tasks until we drop it");
+            let mut found: Vec<Option<u64>> = vec![None; blocks.len()];
+            for output in collect_reraising(&mut queue, tasks_submitted) {
+                if let (index, Some(p)) = output {
+                    found[index] = Some(found[index].map_or(p, |best| best.min(p)));
+                }
+            }
+            for (b, proof) in blocks.iter_mut().zip(found) {
+                if let Some(p) = proof {
+                    b.proof = Some(p);
+                    proofs.insert(b.generation, p);
+                }
+            }
+        }
+        proofs
     }
 
-    pub fn mine_for_proof(self: &Block, workers: usize) -> u64 {
-        let range_start: u64 = 0;
-        let range_end: u64 = 8 * (1 << self.difficulty); // 8 * 2^(bits that must be zero)
-        let chunks: u64 = 2345;
-        self.mine_range(workers, range_start, range_end, chunks)
+    // Does the hash for `proof` fall in [lo, hi]? Hashes compare byte by byte, i.e. big-endian.
+    pub fn is_in_range_for_proof(&self, proof: u64, lo: &Hash, hi: &Hash) -> bool {
+        let hash = self.hash_for_proof(proof);
+        *lo <= hash && hash <= *hi
     }
 
-    pub fn mine(self: &mut Block, workers: usize) {
-        self.proof = Some(self.mine_for_proof(workers));
+    // Mine for a hash inside [lo, hi] instead of one meeting the difficulty, a window of
+    // HASH_RANGE_WINDOW proofs at a time. Like mine, only fails once the whole u64 proof space is
+    // used up (or straight away with EmptyHashRange if lo > hi), and only sets the proof if one was found.
+    pub fn mine_to_range(self: &mut Block<T>, workers: usize, lo: &Hash, hi: &Hash) -> Result<(), MineError> {
+        if lo > hi {
+            return Err(MineError::EmptyHashRange);
+        }
+        let target = MiningTarget::HashRange(*lo, *hi);
+        let proof = Self::search_windows(HASH_RANGE_WINDOW, |range_start, range_end| {
+            let chunks = default_chunks(workers, range_end - range_start);
+            Ok(self.search_range(workers, range_start, range_end, chunks, target.clone(), &|_| {}).proof)
+        })?;
+        self.proof = Some(proof);
+        Ok(())
     }
 }
 
-struct MiningTask {
-    block: sync::Arc<Block>,
+// A block that remembers its hash, for checking the same block over and over (is_valid always
+// hashes again). The hash is computed on the first hash_cached call and forgotten whenever the
+// block is changed, which can only happen through set_proof, set_data or block_mut.
+#[derive(Debug, Clone)]
+pub struct CachedBlock<T = String> {
+    block: Block<T>,
+    hash: sync::OnceLock<Hash>,     // empty until hash_cached, and again after any change
+}
+
+impl<T: Payload> CachedBlock<T> {
+    pub fn new(block: Block<T>) -> CachedBlock<T> {
+        CachedBlock { block, hash: sync::OnceLock::new() }
+    }
+
+    pub fn block(&self) -> &Block<T> {
+        &self.block
+    }
+
+    // Mutable access to the block; the cached hash is dropped, since anything might change
+    pub fn block_mut(&mut self) -> &mut Block<T> {
+        self.hash.take();
+        &mut self.block
+    }
+
+    pub fn into_block(self) -> Block<T> {
+        self.block
+    }
+
+    pub fn set_proof(&mut self, proof: u64) {
+        self.block_mut().set_proof(proof);
+    }
+
+    pub fn set_data(&mut self, data: T) {
+        self.block_mut().set_data(data);
+    }
+
+    // Block::hash, only computed once per change to the block. Panics if it's unmined, like hash.
+    pub fn hash_cached(&self) -> Hash {
+        *self.hash.get_or_init(|| self.block.hash())
+    }
+
+    // is_valid using hash_cached
+    pub fn is_valid_cached(&self) -> bool {
+        self.block.proof.is_some() && self.block.hash_meets_difficulty(&self.hash_cached())
+    }
+}
+
+impl<T: Payload> From<Block<T>> for CachedBlock<T> {
+    fn from(block: Block<T>) -> CachedBlock<T> {
+        CachedBlock::new(block)
+    }
+}
+
+// A panicking task is caught by its worker and produces no output: waiting for one output per
+// task would hang on it. Instead, re-raise it on the caller's thread, as if the work had been
+// done there.
+fn reraise_task_panic<Q: Task + Send + 'static>(queue: &WorkQueue<Q>) {
+    if queue.panicked_tasks() > 0 {
+        panic!("{} mining task(s) panicked", queue.panicked_tasks());
+    }
+}
+
+// Up to `n` outputs (one per task just enqueued), without hanging if a task panics:
+// iter_n stops once every task has settled, then the panic is re-raised
+fn collect_reraising<Q: Task + Send + 'static>(queue: &mut WorkQueue<Q>, n: usize) -> Vec<Q::Output> {
+    let outputs = queue.iter_n(n).collect();
+    reraise_task_panic(queue);
+    outputs
+}
+
+// Each proof is valid with probability 2^-difficulty, so a window of k * 2^difficulty proofs
+// contains a valid one with probability ~1 - e^-k: 63% for k = 1, 95% for k = 3.
+// mine_for_proof_window rolls over to the next window in the rare miss.
+const DEFAULT_WINDOW_MULTIPLE: u64 = 3;
+
+// Proofs mine_to_range searches at a time. Unlike a difficulty, a hash range doesn't say how many
+// proofs a hit takes, so this is just big enough to keep every worker busy for a while.
+const HASH_RANGE_WINDOW: u64 = 1 << 16;
+
+// How often try_mine_for_proof looks at its cancel flag and deadline while the workers run
+const STOP_POLL: Duration = Duration::from_millis(10);
+
+// How many proofs a MiningTask checks between polls of its queue's cancellation flag
+const CANCEL_CHECK_INTERVAL: u64 = 1024;
+
+// Most chunks default_chunks splits a range into. Workers take chunks in order and mine_range stops
+// at the first hit, so small chunks keep everyone near the start of the range instead of deep
+// inside chunks of their own; this many leaves a window of 3 * 2^difficulty proofs in chunks of
+// ~2^difficulty / 780, while a chunk still takes far longer to hash than to queue.
+pub const DEFAULT_CHUNKS: u64 = 2345;
+
+// Fewest proofs default_chunks puts in a chunk: one cancellation check's worth, below which the
+// queue round trip starts to cost about as much as the hashing
+pub const MIN_CHUNK_SIZE: u64 = CANCEL_CHECK_INTERVAL;
+
+// [start, end) as consecutive, non-empty, non-overlapping ranges of ceil(len / chunks) proofs
+// (the last one shorter): at most `chunks` of them, none if the range is empty
+pub(crate) fn chunk_ranges(start: u64, end: u64, chunks: u64) -> impl Iterator<Item = Range<u64>> {
+    let chunk_size = end.saturating_sub(start).div_ceil(chunks.max(1));
+    let mut chunk_start = start;
+    std::iter::from_fn(move || {
+        if chunk_start >= end {
+            return None;
+        }
+        // saturating: start + chunks * chunk_size can be past u64::MAX when end is near it
+        let chunk_end = chunk_start.saturating_add(chunk_size).min(end);
+        let chunk = chunk_start..chunk_end;
+        chunk_start = chunk_end;
+        Some(chunk)
+    })
+}
+
+// Chunk count for mine_range over `range_len` proofs with `workers` threads: DEFAULT_CHUNKS (or
+// 4 per worker, for a pool so big that's more), but fewer so no chunk is smaller than
+// MIN_CHUNK_SIZE, down to 1 for a tiny range.
+pub fn default_chunks(workers: usize, range_len: u64) -> u64 {
+    let most = DEFAULT_CHUNKS.max(4 * workers as u64);
+    (range_len / MIN_CHUNK_SIZE).clamp(1, most)
+}
+
+// How many proofs a StealingTask takes off the shared counter at a time
+const STEAL_BATCH: u64 = 4096;
+
+// What a MiningTask is looking for
+#[derive(Debug, Clone)]
+enum MiningTarget {
+    Difficulty,             // hash meets the block's difficulty
+    HashRange(Hash, Hash),  // hash within [lo, hi]
+}
+
+#[derive(Debug)]
+struct MiningTask<T: Payload> {
+    block: sync::Arc<Block<T>>,
     // TODO: more fields as needed
+    chunk_id: u64,  // which chunk of its search this is, counting up from the start of the range
     start: u64,
-    end: u64
+    end: u64,
+    target: MiningTarget,
+    found: Option<sync::Arc<AtomicBool>>,   // shared by one search's chunks on a reused pool: set on a hit, stop once set
+    best: Option<sync::Arc<AtomicU64>>,     // lowest hit of any chunk so far (u64::MAX = none): stop once past it
+}
+
+impl<T: Payload> MiningTask<T> {
+    fn hits(&self, hash: &Hash) -> bool {
+        match &self.target {
+            MiningTarget::Difficulty => self.block.hash_meets_difficulty(hash),
+            MiningTarget::HashRange(lo, hi) => lo <= hash && hash <= hi,
+        }
+    }
+}
+
+// A MiningTask for par_mine_blocks: always reports, tagged with which block it was mining
+#[derive(Debug)]
+struct TaggedMiningTask<T: Payload> {
+    index: usize,
+    task: MiningTask<T>,
+}
+
+impl<T: Payload> Task for TaggedMiningTask<T> {
+    type Output = (usize, Option<u64>);
+
+    fn run(&self) -> Option<(usize, Option<u64>)> {
+        Some((self.index, self.task.run().and_then(|r| r.proof)))
+    }
+
+    fn total_work(&self) -> u64 {
+        self.task.total_work()
+    }
 }
 
-impl Task for MiningTask {
-    type Output = u64;
+// Always reports, so the caller can count hashes even for chunks that found nothing
+impl<T: Payload> Task for MiningTask<T> {
+    type Output = MiningResult;
 
-    fn run(&self) -> Option<u64> {
+    fn run(&self) -> Option<MiningResult> {
         // TODO: what does it mean to .run?
-        
+        self.run_cancellable(&|| true)
+    }
+
+    fn run_cancellable(&self, should_continue: &dyn Fn() -> bool) -> Option<MiningResult> {
+        let found_elsewhere = || self.found.as_ref().is_some_and(|f| f.load(Ordering::Relaxed));
+        let beaten = |proof: u64| self.best.as_ref().is_some_and(|b| b.load(Ordering::Relaxed) < proof);
+        let hash_for = self.block.proof_hasher();
         // Loop thru range of proofs assigned to this task
         for proof in self.start..self.end {
-            if self.block.is_valid_for_proof(proof) {   // check proofs
-                return Some(proof);
+            let checked = proof - self.start;
+            // checking the flag every proof would be wasteful: only do it every so often
+            if checked.is_multiple_of(CANCEL_CHECK_INTERVAL)
+                && (!should_continue() || found_elsewhere() || beaten(proof))
+            {
+                return Some(MiningResult { proof: None, hashes_checked: checked, winning_chunk: None });
+            }
+            if self.hits(&hash_for(proof)) {   // check proofs
+                if let Some(f) = &self.found {
+                    f.store(true, Ordering::Relaxed);
+                }
+                if let Some(b) = &self.best {
+                    b.fetch_min(proof, Ordering::Relaxed);
+                }
+                return Some(MiningResult {
+                    proof: Some(proof),
+                    hashes_checked: checked + 1,
+                    winning_chunk: Some(self.chunk_id),
+                });
             }
         }
-        None
+        Some(MiningResult { proof: None, hashes_checked: self.end - self.start, winning_chunk: None })
+    }
+
+    fn total_work(&self) -> u64 {
+        self.end - self.start
     }
-}
\ No newline at end of file
+}
+
+// Shared by all the StealingTasks of one mine_range_shared call
+struct StealState<T: Payload> {
+    block: Block<T>,
+    next: AtomicU64,    // start of the next batch nobody has taken yet
+    end: u64,
+    found: AtomicBool,  // somebody has a proof => don't take any more batches
+}
+
+struct StealingTask<T: Payload> {
+    shared: sync::Arc<StealState<T>>,
+}
+
+impl<T: Payload> Task for StealingTask<T> {
+    type Output = (Option<u64>, u64);  // (first proof found, number of batches scanned)
+
+    fn run(&self) -> Option<(Option<u64>, u64)> {
+        let s = &self.shared;
+        let hash_for = s.block.proof_hasher();
+        let mut batches = 0;
+        while !s.found.load(Ordering::SeqCst) {
+            // the cursor stops at end: a plain fetch_add could wrap past u64::MAX back below start
+            let claimed = s.next.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |next| {
+                (next < s.end).then(|| next.saturating_add(STEAL_BATCH).min(s.end))
+            });
+            let batch_start = match claimed {
+                Ok(batch_start) => batch_start,
+                Err(_) => break,
+            };
+            batches += 1;
+            let batch_end = batch_start.saturating_add(STEAL_BATCH).min(s.end);
+            if let Some(proof) = (batch_start..batch_end).find(|&p| s.block.hash_meets_difficulty(&hash_for(p))) {
+                s.found.store(true, Ordering::SeqCst);
+                return Some((Some(proof), batches));
+            }
+        }
+        Some((None, batches))
+    }
+}
@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod block_tests {
     use crate::block::*;
 
@@ -20,6 +21,11 @@ mod block_tests {
             prev_hash: Hash::from([10; 32]),
             data: "Cool Data".to_string(),
             proof: Option::None,
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
         };
         assert_eq!("0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a:3:13:Cool Data:4321"
                   ,b0.hash_string_for_proof(4321))
@@ -33,6 +39,11 @@ mod block_tests {
             prev_hash: Hash::from([10; 32]),
             data: "Cool Data".to_string(),
             proof: Option::None,
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
         };
         assert_eq!(Hash::from([
                         99, 66, 200, 198, 96, 57, 238, 158, 136, 127, 33, 80, 24, 122, 108, 205,
@@ -49,6 +60,11 @@ mod block_tests {
             prev_hash: Hash::from([10; 32]),
             data: "Cool Data".to_string(),
             proof: Option::Some(102020),
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
         };
         let b1 : Block = Block::next(&b0,"Cooler data".to_string());
         assert_eq!(b1.difficulty, 13);
@@ -82,6 +98,52 @@ mod block_tests {
                         ])))
     }
 
+    // all 0xff except the last `zeros` bytes, with `low` as the byte just before them
+    fn hash_ending(low: u8, zeros: usize) -> Hash {
+        let mut h = Hash::from([0xff; 32]);
+        h[31 - zeros] = low;
+        for i in 0..zeros {
+            h[31 - i] = 0;
+        }
+        h
+    }
+
+    #[test]
+    fn hash_satisfies_difficulty_1_bit() {
+        assert!(Block::hash_satisfies_difficulty(1, hash_ending(0b1111_1110, 0)));
+        assert!(!Block::hash_satisfies_difficulty(1, hash_ending(0b0000_0001, 0)));
+        // only the last byte's bits count, not the bytes before it
+        assert!(Block::hash_satisfies_difficulty(1, hash_ending(0b0000_0010, 0)));
+    }
+
+    #[test]
+    fn hash_satisfies_difficulty_7_bits() {
+        assert!(Block::hash_satisfies_difficulty(7, hash_ending(0b1000_0000, 0)));
+        assert!(!Block::hash_satisfies_difficulty(7, hash_ending(0b0100_0000, 0)));
+        assert!(!Block::hash_satisfies_difficulty(7, hash_ending(0b1000_0001, 0)));
+    }
+
+    #[test]
+    fn hash_satisfies_difficulty_8_bits() {
+        assert!(Block::hash_satisfies_difficulty(8, hash_ending(0xff, 1)));
+        assert!(!Block::hash_satisfies_difficulty(8, hash_ending(0b1000_0000, 0)));
+    }
+
+    #[test]
+    fn hash_satisfies_difficulty_9_bits() {
+        assert!(Block::hash_satisfies_difficulty(9, hash_ending(0b1111_1110, 1)));
+        assert!(!Block::hash_satisfies_difficulty(9, hash_ending(0b1111_1111, 1)));
+        // the old fixed mask (1 << n_bits) only looked at bit 1
+        assert!(!Block::hash_satisfies_difficulty(9, hash_ending(0b0000_0001, 1)));
+    }
+
+    #[test]
+    fn hash_satisfies_difficulty_16_bits() {
+        assert!(Block::hash_satisfies_difficulty(16, hash_ending(0xff, 2)));
+        assert!(!Block::hash_satisfies_difficulty(16, hash_ending(0b1000_0000, 1)));
+        assert!(Block::hash_satisfies_difficulty(16, hash_ending(0, 3)));
+    }
+
     #[test]
     fn mine_basic_0() {
         let mut b0: Block = Block {
@@ -90,8 +152,1022 @@ mod block_tests {
             prev_hash: Hash::from([10; 32]),
             data: "Cool Data".to_string(),
             proof: Option::Some(102020),
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
+        };
+        b0.mine(4).unwrap();
+        assert!(b0.is_valid());
+    }
+
+    #[test]
+    fn hash_string_for_proof_coinbase_0() {
+        let mut b0: Block = Block {
+            difficulty: 13,
+            generation: 3,
+            prev_hash: Hash::from([10; 32]),
+            data: "Cool Data".to_string(),
+            proof: Option::None,
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
+        };
+        b0.set_coinbase(50, "alice".to_string());
+        assert_eq!("0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a:3:13:Cool Data:50:alice:4321"
+                  ,b0.hash_string_for_proof(4321))
+    }
+
+    #[test]
+    fn mine_coinbase_0() {
+        let mut b0: Block = Block::initial(12);
+        b0.set_coinbase(50, "alice".to_string());
+        b0.mine(4).unwrap();
+        assert!(b0.is_valid());
+        let mined_hash = b0.hash();
+
+        // Claiming a bigger reward after the fact changes the hash and breaks the proof.
+        b0.coinbase.as_mut().unwrap().reward = 5000;
+        assert_ne!(b0.hash(), mined_hash);
+        assert!(!b0.is_valid());
+    }
+
+    #[test]
+    fn is_valid_difficulty_0() {
+        let mut b0: Block = Block::initial(0);
+        assert!(!b0.is_valid());
+        for p in [0, 1, 98765, u64::MAX] {
+            b0.set_proof(p);
+            assert!(b0.is_valid());
+            assert_eq!(b0.is_valid(), b0.is_valid_for_proof(p));
+        }
+    }
+
+    #[test]
+    fn try_hash_0() {
+        let mut b0: Block = Block::initial(13);
+        assert_eq!(b0.try_hash(), None);
+        assert_eq!(b0.try_hash_string(), None);
+
+        b0.set_proof(4321);
+        assert_eq!(b0.try_hash(), Some(b0.hash()));
+        assert_eq!(b0.try_hash_string(), Some(b0.hash_string()));
+    }
+
+    #[test]
+    fn hash_high_u128_0() {
+        let mut h0 = Hash::from([10; 32]);
+        let mut h1 = Hash::from([10; 32]);
+        h0[31] = 0;
+        h1[16] = 255;
+        assert_eq!(hash_high_u128(&h0), 0x0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a);
+        assert_eq!(hash_high_u128(&h0), hash_high_u128(&h1));
+
+        h1[15] = 11;
+        assert_eq!(hash_high_u128(&h1), 0x0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0b);
+    }
+
+    #[test]
+    fn same_content_0() {
+        let b0: Block = Block {
+            difficulty: 13,
+            generation: 3,
+            prev_hash: Hash::from([10; 32]),
+            data: "Cool Data".to_string(),
+            proof: Option::Some(102020),
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
+        };
+        let mut b1 = b0.clone();
+        b1.set_proof(4321);
+        assert!(b0.same_content(&b1));
+
+        b1.data = "Other Data".to_string();
+        assert!(!b0.same_content(&b1));
+
+        let mut b2 = b0.clone();
+        b2.set_coinbase(50, "alice".to_string());
+        assert!(!b0.same_content(&b2));
+    }
+
+    #[test]
+    fn meets_difficulty_0() {
+        let mut b0: Block = Block::initial(8);
+        assert!(!b0.meets_difficulty());
+        b0.mine_serial();
+        assert!(b0.meets_difficulty());
+    }
+
+    #[test]
+    fn mine_first_window_0() {
+        // a 3 * 2^10 window should hold a valid proof ~95% of the time. The inputs are pinned
+        // (timestamp 0, fixed data), so which windows hit is too: all but "block 4"'s.
+        let difficulty = 10;
+        let window = 3 * (1 << difficulty);
+        let b0 = b_mined(difficulty);
+        assert_eq!(b0.timestamp, 0);
+        for i in 0..40 {
+            let b = Block::next(&b0, format!("block {}", i));
+            assert_eq!(b.timestamp, 0);
+            let proof = b.mine_range(4, 0, window, 64);
+            if i == 4 {
+                assert_eq!(proof, window, "block 4's first window has no proof");
+            } else {
+                assert!(proof < window, "block {}'s first window should hold a proof", i);
+                assert!(b.is_valid_for_proof(proof));
+            }
+        }
+    }
+
+    #[test]
+    fn mine_for_proof_window_0() {
+        // a single 2^difficulty window misses over a third of the time: still always valid
+        let b0: Block = Block::initial(12);
+        let proof = b0.mine_for_proof_window(4, 1).unwrap();
+        assert!(b0.is_valid_for_proof(proof));
+        // a multiple of 0 searches 2^difficulty windows rather than spinning on empty ones
+        let proof = b0.mine_for_proof_window(4, 0).unwrap();
+        assert!(b0.is_valid_for_proof(proof));
+    }
+
+    #[test]
+    fn mine_for_proof_tuned_0() {
+        let b0: Block = Block::initial(12);
+        for chunks in [1, 7, 2345] {
+            assert!(b0.is_valid_for_proof(b0.mine_for_proof_tuned(4, chunks).unwrap()));
+        }
+    }
+
+    #[test]
+    fn chunk_ranges_0() {
+        let cases = [
+            (0, 100, 4),
+            (0, 100, 7),
+            (5, 17, 5),
+            (10, 13, 10), // more chunks than proofs
+            (3, 3, 4),
+            (0, 1 << 20, 2345),
+            (u64::MAX - 10, u64::MAX, 3),
+            (0, u64::MAX, 3),
+            // the window of a difficulty 63+ block is all of [0, u64::MAX), which an even count doesn't divide
+            (0, u64::MAX, 2),
+            (0, u64::MAX, 16),
+            (0, u64::MAX, DEFAULT_CHUNKS),
+        ];
+        for (start, end, chunks) in cases {
+            let ranges: Vec<_> = chunk_ranges(start, end, chunks).collect();
+            assert!(ranges.len() as u64 <= chunks);
+            // consecutive and non-empty => their union is exactly [start, end), with no overlaps
+            let mut expected_start = start;
+            for r in &ranges {
+                assert_eq!(r.start, expected_start, "{:?} in {:?}", r, (start, end, chunks));
+                assert!(r.start < r.end);
+                expected_start = r.end;
+            }
+            assert_eq!(expected_start, end);
+        }
+    }
+
+    #[test]
+    fn default_chunks_0() {
+        assert_eq!(default_chunks(4, 0), 1);
+        assert_eq!(default_chunks(4, 100), 1);
+        assert_eq!(default_chunks(4, 10 * MIN_CHUNK_SIZE), 10);
+        assert_eq!(default_chunks(4, 3 << 30), DEFAULT_CHUNKS);
+        assert_eq!(default_chunks(1000, u64::MAX), 4000);
+    }
+
+    #[test]
+    fn mine_to_range_0() {
+        // bottom quarter of the hash space, by the leading byte
+        let lo = Hash::from([0; 32]);
+        let mut hi = Hash::from([255; 32]);
+        hi[0] = 0x3f;
+
+        let mut b0: Block = Block::initial(20);
+        b0.mine_to_range(4, &lo, &hi).unwrap();
+        let proof = b0.proof.unwrap();
+        assert!(b0.is_in_range_for_proof(proof, &lo, &hi));
+        assert!(b0.hash()[0] <= 0x3f);
+
+        // lo above hi: nothing to search for, and the block is left alone
+        let mut b1: Block = Block::initial(20);
+        assert_eq!(b1.mine_to_range(4, &hi, &lo), Err(MineError::EmptyHashRange));
+        assert_eq!(b1.proof, None);
+    }
+
+    #[test]
+    fn par_mine_blocks_0() {
+        let b0 = b_mined(10);
+        let b1 = Block::next(&b0, "one".to_string());
+        let mut b2 = Block::next(&b0, "two".to_string());
+        b2.generation = 7;
+        let mut b3 = Block::initial(12);
+        b3.generation = 9;
+        let mut blocks = vec![b1, b2, b3];
+
+        let proofs = Block::par_mine_blocks(&mut blocks, 4);
+        assert_eq!(proofs.len(), 3);
+        for b in &blocks {
+            assert!(b.is_valid());
+            assert_eq!(proofs[&b.generation], b.proof.unwrap());
+            // chunks past a block's first hit stop early, but it's still the lowest
+            let mut serial = Block { proof: None, ..b.clone() };
+            serial.mine_serial();
+            assert_eq!(serial.proof, b.proof);
+        }
+    }
+
+    #[test]
+    fn hash_string_for_proof_extra_nonce_0() {
+        let mut b0: Block = Block {
+            difficulty: 13,
+            generation: 3,
+            prev_hash: Hash::from([10; 32]),
+            data: "Cool Data".to_string(),
+            proof: Option::None,
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
+        };
+        b0.extra_nonce = 2;
+        assert_eq!("0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a:3:13:Cool Data:x2:4321"
+                  ,b0.hash_string_for_proof(4321))
+    }
+
+    #[test]
+    fn mine_with_extra_nonce_0() {
+        // 64 proofs at difficulty 12 rarely hold a valid one; pick a timestamp where they don't,
+        // so this must roll over
+        let mut b0: Block = Block::initial(12);
+        while b0.mine_range(1, 0, 64, 1) < 64 {
+            b0.timestamp += 1;
+        }
+        b0.mine_with_extra_nonce(4, 64);
+        assert!(b0.extra_nonce > 0);
+        assert!(b0.proof.unwrap() < 64);
+        assert!(b0.is_valid());
+    }
+
+    #[test]
+    #[should_panic(expected = "needs a window")]
+    fn mine_with_extra_nonce_empty_window_0() {
+        let mut b0: Block = Block::initial(12);
+        b0.mine_with_extra_nonce(4, 0);
+    }
+
+    #[test]
+    fn mine_range_repeated_0() {
+        // each search gets a fresh cancellation flag: stopping one doesn't poison the next
+        let b0: Block = Block::initial(14);
+        let first = b0.mine_range(4, 0, 1 << 22, 8);
+        let second = b0.mine_range(4, 0, 1 << 22, 8);
+        assert!(b0.is_valid_for_proof(first));
+        assert!(b0.is_valid_for_proof(second));
+    }
+
+    #[test]
+    fn mine_range_shared_0() {
+        let b0: Block = Block::initial(16);
+        let mut serial: Block = b0.clone();
+        serial.mine_serial();
+        assert_eq!(b0.mine_range_shared(4, 0, 1 << 24), serial.proof.unwrap());
+
+        // nothing valid in a tiny range => end
+        assert_eq!(Block::initial(40).mine_range_shared(4, 0, 1), 1);
+
+        // the batch cursor stops at end instead of wrapping past u64::MAX to below start
+        let start = u64::MAX - 10000;
+        let p = b0.mine_range_shared(4, start, u64::MAX);
+        assert!(p >= start);
+        assert!(p == u64::MAX || b0.is_valid_for_proof(p));
+        assert_eq!(Block::initial(40).mine_range_shared(4, start, u64::MAX), u64::MAX);
+    }
+
+    #[test]
+    fn steal_range_balance_0() {
+        // with the proof several batches in, the work gets spread over the workers
+        let b0: Block = Block::initial(18);
+        let (proof, batches) = b0.steal_range(4, 0, 1 << 24);
+        assert!(b0.is_valid_for_proof(proof));
+        assert_eq!(batches.len(), 4);
+        // batches below the winner were all taken by someone
+        assert!(batches.iter().sum::<u64>() * 4096 > proof);
+        if proof >= 4 * 4096 {
+            assert!(batches.iter().filter(|&&n| n > 0).count() > 1, "unbalanced: {:?}", batches);
+        }
+    }
+
+    #[test]
+    fn try_mine_range_0() {
+        let b0: Block = Block::initial(16);
+        assert_eq!(Block::initial(40).try_mine_range(4, 0, 16, 4), Err(MineError::RangeExhausted { start: 0, end: 16 }));
+
+        let proof = b0.try_mine_range(4, 0, 1 << 22, 64).unwrap();
+        assert!(b0.is_valid_for_proof(proof));
+    }
+
+    #[test]
+    fn try_initial_0() {
+        assert!(Block::try_initial(0).is_ok());
+        assert_eq!(Block::try_initial(MAX_DIFFICULTY).unwrap().difficulty, MAX_DIFFICULTY);
+        assert_eq!(Block::try_initial(MAX_DIFFICULTY + 1).unwrap_err(), InvalidDifficulty(MAX_DIFFICULTY + 1));
+        assert!(Block::try_initial(255).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "above the supported maximum")]
+    fn initial_invalid_0() {
+        Block::initial(200);
+    }
+
+    #[test]
+    fn hash_for_proof_with_0() {
+        let b0: Block = Block::initial(8);
+        assert_eq!(b0.hash_for_proof_with::<sha2::Sha256>(1234), b0.hash_for_proof(1234));
+        assert_eq!(b0.hash_for_proof_with::<sha2::Sha512>(1234).len(), 64);
+    }
+
+    #[test]
+    fn mine_serial_with_0() {
+        let mut b256 = Block::initial(12);
+        let mut serial = b256.clone();
+        serial.mine_serial();
+        b256.mine_serial_with::<sha2::Sha256>();
+        assert_eq!(b256.proof, serial.proof);
+        assert!(b256.is_valid());
+
+        let mut b512 = Block::initial(12);
+        b512.mine_serial_with::<sha2::Sha512>();
+        let p = b512.proof.unwrap();
+        assert!(b512.is_valid_for_proof_with::<sha2::Sha512>(p));
+        assert!(Block::bytes_satisfy_difficulty(12, &b512.hash_for_proof_with::<sha2::Sha512>(p)));
+    }
+
+    #[test]
+    fn bytes_satisfy_difficulty_0() {
+        assert!(Block::bytes_satisfy_difficulty(8, &[0xff, 0x00]));
+        assert!(!Block::bytes_satisfy_difficulty(17, &[0x00, 0x00]));
+    }
+
+    #[test]
+    fn hash_string_for_proof_timestamp_0() {
+        let mut b0: Block = Block {
+            difficulty: 13,
+            generation: 3,
+            prev_hash: Hash::from([10; 32]),
+            data: "Cool Data".to_string(),
+            proof: Option::None,
+            coinbase: None,
+            extra_nonce: 2,
+            timestamp: 1700000000,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
+        };
+        assert_eq!("0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a0a:3:13:Cool Data:x2:t1700000000:4321"
+                  ,b0.hash_string_for_proof(4321));
+        let with_time = b0.hash_for_proof(4321);
+        b0.timestamp = 1700000001;
+        assert_ne!(with_time, b0.hash_for_proof(4321));
+    }
+
+    #[test]
+    fn next_at_0() {
+        let b0 = b_mined(8);
+        let b1 = Block::next_at(&b0, "later".to_string(), 1700000000);
+        assert_eq!(b1.timestamp, 1700000000);
+        assert!(b1.same_content(&Block::next_at(&b0, "later".to_string(), 1700000000)));
+        assert!(!b1.same_content(&Block::next_at(&b0, "later".to_string(), 1700000001)));
+        // next/initial leave it at 0 (and out of the hash string); next_now/initial_at set it
+        assert_eq!(Block::next(&b0, "then".to_string()).timestamp, 0);
+        assert_eq!(b0.timestamp, 0);
+        assert!(!b0.hash_string_for_proof(0).contains(":t"));
+        assert!(Block::next_now(&b0, "now".to_string()).timestamp >= 1700000000);
+        assert_eq!(Block::initial_at(8, 1700000000).timestamp, 1700000000);
+    }
+
+    #[test]
+    fn mine_range_counted_0() {
+        let b0: Block = Block::initial(12);
+        let mut serial = b0.clone();
+        serial.mine_serial();
+        let p = serial.proof.unwrap();
+
+        // one worker, one chunk => exactly the proofs up to and including the hit
+        let r = b0.mine_range_counted(1, 0, 1 << 20, 1);
+        assert_eq!(r, MiningResult { proof: Some(p), hashes_checked: p + 1, winning_chunk: Some(0) });
+
+        // nothing valid => every proof in the range got hashed
+        let r = Block::initial(40).mine_range_counted(4, 0, 100, 4);
+        assert_eq!(r, MiningResult { proof: None, hashes_checked: 100, winning_chunk: None });
+
+        let r = b0.mine_range_counted(4, 0, 1 << 20, 64);
+        assert!(b0.is_valid_for_proof(r.proof.unwrap()));
+        assert!(r.hashes_checked >= 1 && r.hashes_checked <= 1 << 20);
+    }
+
+    #[test]
+    fn mine_range_with_progress_0() {
+        use std::cell::RefCell;
+        // nothing valid => every chunk comes back empty, counted one at a time
+        let b0: Block = Block::initial(40);
+        for workers in [1, 3] {
+            let reports = RefCell::new(Vec::new());
+            let p = b0.mine_range_with_progress(workers, 0, 100, 4, |n| reports.borrow_mut().push(n));
+            assert_eq!(p, 100);
+            assert_eq!(reports.into_inner(), vec![1, 2, 3, 4]);
+        }
+
+        // the chunk that hits isn't reported
+        let b0: Block = Block::initial(8);
+        let reports = RefCell::new(Vec::new());
+        let p = b0.mine_range_with_progress(1, 0, 1 << 16, 16, |n| reports.borrow_mut().push(n));
+        assert!(b0.is_valid_for_proof(p));
+        assert_eq!(reports.into_inner().len() as u64, p / (1 << 12));
+    }
+
+    #[test]
+    fn mine_range_winner_0() {
+        let b0: Block = Block::initial(12);
+        let mut serial = b0.clone();
+        serial.mine_serial();
+        let p = serial.proof.unwrap();
+
+        // one worker scans the chunks in order => the lowest proof, in the chunk holding it
+        let chunk_size = 1 << 10;
+        assert_eq!(b0.mine_range_winner(1, 0, 1 << 20, 1 << 10), Some((p, p / chunk_size)));
+
+        let (proof, chunk) = b0.mine_range_winner(4, 0, 1 << 20, 1 << 10).unwrap();
+        assert!(b0.is_valid_for_proof(proof));
+        assert_eq!(chunk, proof / chunk_size);
+        assert_eq!(Block::initial(40).mine_range_winner(4, 0, 100, 4), None);
+    }
+
+    #[test]
+    fn first_invalid_proof_0() {
+        let b0: Block = Block::initial(20);
+        // shares only need 8 bits, far less than the block's own difficulty
+        let meets = |p: u64| Block::hash_satisfies_difficulty(8, b0.hash_for_proof(p));
+        let share: Vec<u64> = (0..).filter(|&p| meets(p)).take(3).collect();
+        let bad = (0..).find(|&p| !meets(p)).unwrap();
+
+        assert_eq!(b0.first_invalid_proof(&share, 8), None);
+        assert_eq!(b0.first_invalid_proof(&[], 8), None);
+        assert_eq!(b0.first_invalid_proof(&[share[0], share[1], bad, share[2], bad], 8), Some(2));
+        assert_eq!(b0.first_invalid_proof(&[bad], 0), None);
+    }
+
+    #[test]
+    fn mine_range_lowest_0() {
+        let b0: Block = Block::initial(12);
+        let mut serial = b0.clone();
+        serial.mine_serial();
+        // many small chunks => plenty of chunks above the lowest one also hit
+        assert_eq!(b0.mine_range_lowest(4, 0, 1 << 20, 256), serial.proof);
+        assert_eq!(b0.mine_range_lowest(4, 0, 1 << 20, 1), serial.proof);
+        assert_eq!(Block::initial(40).mine_range_lowest(4, 0, 100, 4), None);
+    }
+
+    #[test]
+    fn mine_range_coordinated_0() {
+        let b0: Block = Block::initial(12);
+        let mut serial = b0.clone();
+        serial.mine_serial();
+        // still the lowest, however the range is split
+        assert_eq!(b0.mine_range_coordinated(4, 0, 1 << 20, 2345), serial.proof);
+        assert_eq!(b0.mine_range_coordinated(4, 0, 1 << 20, 1), serial.proof);
+        assert_eq!(b0.mine_range_coordinated(1, 0, 1 << 20, 16), serial.proof);
+        assert_eq!(Block::initial(40).mine_range_coordinated(4, 0, 100, 4), None);
+    }
+
+    #[test]
+    fn valid_proofs_in_0() {
+        let b0: Block = Block::initial(6);
+        let proofs = b0.valid_proofs_in(0, 1 << 16, 4);
+        let expected: Vec<u64> = (0..1 << 16).filter(|&p| b0.is_valid_for_proof(p)).collect();
+        assert_eq!(proofs, expected);
+        // 1 in 64 of 65536 => about 1024
+        assert!(proofs.len() > 800 && proofs.len() < 1250, "{}", proofs.len());
+
+        let mut serial = b0.clone();
+        serial.mine_serial();
+        assert_eq!(proofs[0], serial.proof.unwrap());
+        assert_eq!(b0.valid_proofs_in(5, 5, 4), Vec::<u64>::new());
+        assert!(Block::initial(40).valid_proofs_in(0, 100, 2).is_empty());
+    }
+
+    #[test]
+    fn mine_one_worker_0() {
+        // the single-worker path skips the queue but must find the same proof
+        let mut b0: Block = Block::initial(14);
+        let mut serial = b0.clone();
+        serial.mine_serial();
+        b0.mine(1).unwrap();
+        assert_eq!(b0.proof, serial.proof);
+
+        let b1 = Block::next(&b0, "next".to_string());
+        assert_eq!(b1.mine_range(1, 0, 1 << 20, 16), b1.mine_range_lowest(4, 0, 1 << 20, 16).unwrap());
+        assert_eq!(Block::initial(40).mine_range_counted(1, 0, 100, 4).hashes_checked, 100);
+
+        // and mines on this thread, with no queue: only this thread ever hashes the block,
+        // where a one-worker queue hashes it on its worker (benches/mining.rs times the two)
+        let here = std::thread::current().id();
+        let traced = Traced::new("one worker");
+        let mut direct = Block::initial_with(14, traced.clone()).unwrap();
+        direct.mine(1).unwrap();
+        assert_eq!(traced.hashed_on(), vec![here]);
+
+        let traced = Traced::new("one worker");
+        let queued = Block::initial_with(14, traced.clone()).unwrap();
+        assert_eq!(queued.mine_range_lowest(1, 0, queued.window_size(3), 1), direct.proof);
+        assert!(!traced.hashed_on().contains(&here));
+    }
+
+    #[test]
+    fn hash_to_hex_0() {
+        let mut h = Hash::from([0; 32]);
+        h[0] = 0x0a;
+        h[31] = 0xff;
+        let hex = hash_to_hex(&h);
+        assert_eq!(hex.len(), 64);
+        assert_eq!(hex, format!("0a{}ff", "0".repeat(60)));
+        // what the hash string used to format with
+        assert_eq!(hex, format!("{:02x}", h));
+    }
+
+    #[test]
+    fn display_0() {
+        let mut b0: Block = Block {
+            difficulty: 13,
+            generation: 3,
+            prev_hash: Hash::from([10; 32]),
+            data: "Cool Data".to_string(),
+            proof: Option::None,
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
+        };
+        assert_eq!(b0.to_string(), "block 3 (difficulty 13, prev 0a0a0a0a..): Cool Data, <unmined>");
+        b0.set_proof(4321);
+        assert_eq!(b0.to_string(), "block 3 (difficulty 13, prev 0a0a0a0a..): Cool Data, proof 4321");
+    }
+
+    #[test]
+    fn mine_chain_0() {
+        let genesis = Block::initial(10);
+        let mut blocks = vec![genesis.clone()];
+        for i in 1..20 {
+            // placeholder prev_hash: mine_chain links each block once the one before it is mined
+            blocks.push(Block::next_at(&b_mined(1), format!("block {}", i), 0));
+            blocks[i].generation = i as u64;
+            blocks[i].difficulty = 10;
+        }
+
+        Block::mine_chain(&mut blocks, 4).unwrap();
+        assert!(blocks.iter().all(|b| b.is_valid()));
+        for pair in blocks.windows(2) {
+            assert_eq!(pair[1].prev_hash, pair[0].hash());
+        }
+        assert!(blocks[0].same_content(&genesis));
+
+        // one pool for the whole run: every block is mined on the same 4 workers, where mining
+        // the blocks one at a time starts 4 new ones per block (benches/mining.rs times the two).
+        // This thread only hashes each block to link the next one.
+        let here = std::thread::current().id();
+        let traced = Traced::new("pooled");
+        let cheap: Vec<_> = (0..20)
+            .map(|i| Block { generation: i, ..Block::initial_with(0, traced.clone()).unwrap() })
+            .collect();
+        let mut pooled = cheap.clone();
+        Block::mine_chain(&mut pooled, 4).unwrap();
+        let workers_used = traced.hashed_on().into_iter().filter(|t| *t != here).count();
+        assert!((1..=4).contains(&workers_used), "{} worker threads", workers_used);
+
+        let traced = Traced::new("pooled");
+        let mut separate: Vec<_> = cheap.into_iter().map(|b| Block { data: traced.clone(), ..b }).collect();
+        for i in 0..separate.len() {
+            if i > 0 {
+                separate[i].prev_hash = separate[i - 1].hash();
+            }
+            separate[i].mine(4).unwrap();
+        }
+        assert_eq!(pooled.last().unwrap().hash(), separate.last().unwrap().hash());
+        // each block was mined by a pool of its own, and thread ids are never reused
+        assert!(traced.hashed_on().len() > separate.len());
+    }
+
+    #[test]
+    fn no_workers_0() {
+        // 0 workers is treated as 1 instead of waiting forever for a worker that doesn't exist
+        let b0: Block = Block::initial(6);
+        assert_eq!(b0.valid_proofs_in(0, 1 << 10, 0), b0.valid_proofs_in(0, 1 << 10, 1));
+
+        let mut blocks = vec![Block::next_at(&b_mined(1), String::from("a"), 0)];
+        Block::par_mine_blocks(&mut blocks, 0);
+        assert!(blocks[0].is_valid());
+
+        let mut blocks = vec![b0.clone(), Block::next_at(&b_mined(1), String::from("b"), 0)];
+        Block::mine_chain(&mut blocks, 0).unwrap();
+        assert!(blocks.iter().all(|b| b.is_valid()));
+
+        // every other way in too
+        use std::time::Duration;
+        let lowest = b0.mine_range_lowest(1, 0, 1 << 12, 4).unwrap();
+        assert_eq!(b0.mine_range(0, 0, 1 << 12, 4), lowest);
+        assert_eq!(b0.mine_range_lowest(0, 0, 1 << 12, 4), Some(lowest));
+        assert_eq!(b0.mine_range_coordinated(0, 0, 1 << 12, 4), Some(lowest));
+        assert_eq!(b0.mine_range_timeout(0, 0, 1 << 12, 4, Duration::from_secs(5)), Some(lowest));
+        assert_eq!(b0.mine_range_shared(0, 0, 1 << 12), lowest);
+        let mut b = b0.clone();
+        b.mine(0).unwrap();
+        assert!(b.is_valid());
+        let mut b = b0.clone();
+        b.mine_with_extra_nonce(0, 1 << 12);
+        assert!(b.is_valid());
+    }
+
+    #[test]
+    fn separated_hash_0() {
+        let mut b0: Block = Block {
+            difficulty: 13,
+            generation: 3,
+            prev_hash: Hash::from([10; 32]),
+            data: "Cool Data".to_string(),
+            proof: None,
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Separated,
+            difficulty_mode: DifficultyMode::Trailing,
+        };
+        // sha256("cmpt383-block-v1" || H(header) || H("Cool Data") || 4321 as 8 LE bytes)
+        assert_eq!(hash_to_hex(&b0.hash_for_proof(4321)),
+                   "f0057e69288385b7d94f0971acbdb8c6b1fdacd9c96a919d11d73291044c60dd");
+        let flat = Block { hash_scheme: HashScheme::Flat, ..b0.clone() };
+        assert_ne!(b0.hash_for_proof(4321), flat.hash_for_proof(4321));
+
+        // first_invalid_proof's midstate has to follow the scheme too
+        b0.difficulty = 8;
+        b0.mine_serial();
+        let p = b0.proof.unwrap();
+        assert!(b0.is_valid());
+        assert_eq!(b0.first_invalid_proof(&[p], 8), None);
+        assert!(Block::next(&b0, "next".to_string()).hash_scheme == HashScheme::Separated);
+    }
+
+    #[test]
+    fn separated_hash_no_field_collision_0() {
+        // data with colons can impersonate a coinbase in the flat hash string
+        let mut with_coinbase: Block = Block {
+            difficulty: 13,
+            generation: 3,
+            prev_hash: Hash::from([10; 32]),
+            data: "x".to_string(),
+            proof: None,
+            coinbase: Some(Coinbase { reward: 1, miner: "m".to_string() }),
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
+        };
+        let mut colons = Block { data: "x:1:m".to_string(), coinbase: None, ..with_coinbase.clone() };
+        assert_eq!(with_coinbase.hash_for_proof(7), colons.hash_for_proof(7));
+
+        with_coinbase.hash_scheme = HashScheme::Separated;
+        colons.hash_scheme = HashScheme::Separated;
+        assert_ne!(with_coinbase.hash_for_proof(7), colons.hash_for_proof(7));
+    }
+
+    #[test]
+    fn window_size_0() {
+        let mut b0: Block = Block::initial(10);
+        assert_eq!(b0.window_size(3), 3 << 10);
+        b0.difficulty = 62;
+        assert_eq!(b0.window_size(3), 3 << 62);
+        // 3 * 2^63 and 2^255 don't fit: the window is the whole proof space
+        b0.difficulty = 63;
+        assert_eq!(b0.window_size(3), u64::MAX);
+        b0.difficulty = 255;
+        assert_eq!(b0.window_size(1), u64::MAX);
+        // an empty window would never advance
+        b0.difficulty = 10;
+        assert_eq!(b0.window_size(0), 1 << 10);
+    }
+
+    #[test]
+    fn mine_range_timeout_0() {
+        use std::time::{Duration, Instant};
+
+        // hopeless: ~2^40 hashes needed. Returning at all means the workers were stopped and joined.
+        let start = Instant::now();
+        let hard = Block::initial(40);
+        assert_eq!(hard.mine_range_timeout(4, 0, u64::MAX, 64, Duration::from_millis(100)), None);
+        assert!(Instant::now().duration_since(start) < Duration::from_secs(5));
+
+        let b0: Block = Block::initial(12);
+        let proof = b0.mine_range_timeout(4, 0, 1 << 22, 64, Duration::from_secs(60)).unwrap();
+        assert!(b0.is_valid_for_proof(proof));
+    }
+
+    #[test]
+    fn parse_hash_0() {
+        let h = b_mined(8).hash();
+        assert_eq!(parse_hash(&hash_to_hex(&h)), Ok(h));
+        assert_eq!(parse_hash(&hash_to_hex(&h).to_uppercase()), Ok(h));
+        assert_eq!(parse_hash(&"0a".repeat(32)), Ok(Hash::from([10; 32])));
+
+        assert_eq!(parse_hash(""), Err(ParseHashError::WrongLength(0)));
+        assert_eq!(parse_hash(&"0".repeat(65)), Err(ParseHashError::WrongLength(65)));
+        assert_eq!(parse_hash(&format!("{}g", "0".repeat(63))), Err(ParseHashError::InvalidHex { index: 63 }));
+        // lengths are in chars, however many bytes they take
+        assert_eq!(parse_hash(&format!("é{}", "0".repeat(62))), Err(ParseHashError::WrongLength(63)));
+        assert_eq!(parse_hash(&format!("é{}", "0".repeat(63))), Err(ParseHashError::InvalidHex { index: 0 }));
+    }
+
+    #[test]
+    fn byte_payload_0() {
+        let text: Block = Block {
+            difficulty: 13,
+            generation: 3,
+            prev_hash: Hash::from([10; 32]),
+            data: "Cool Data".to_string(),
+            proof: None,
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
         };
-        b0.mine(4);
+        let bytes: Block<Vec<u8>> = Block {
+            difficulty: text.difficulty,
+            generation: text.generation,
+            prev_hash: text.prev_hash,
+            data: b"Cool Data".to_vec(),
+            proof: None,
+            coinbase: None,
+            extra_nonce: 0,
+            timestamp: 0,
+            hash_scheme: HashScheme::Flat,
+            difficulty_mode: DifficultyMode::Trailing,
+        };
+        // the same bytes hash the same, whatever type holds them
+        assert_eq!(bytes.hash_for_proof(4321), text.hash_for_proof(4321));
+        assert_eq!(bytes.hash_string_for_proof(4321), text.hash_string_for_proof(4321));
+
+        // invalid UTF-8 looks the same in the (lossy) hash string but hashes by its real bytes
+        let ff = Block { data: vec![0xff], ..bytes.clone() };
+        let fe = Block { data: vec![0xfe], ..bytes.clone() };
+        assert_eq!(ff.hash_string_for_proof(1), fe.hash_string_for_proof(1));
+        assert_ne!(ff.hash_for_proof(1), fe.hash_for_proof(1));
+    }
+
+    #[test]
+    fn byte_payload_mine_0() {
+        let mut b0: Block<Vec<u8>> = Block::initial_with(12, vec![1, 2, 3]).unwrap();
+        b0.mine(4).unwrap();
         assert!(b0.is_valid());
+        let mut b1 = Block::next(&b0, vec![0, 255]);
+        b1.hash_scheme = HashScheme::Separated;
+        b1.mine(4).unwrap();
+        assert!(b1.is_valid());
+        assert_eq!(b1.prev_hash, b0.hash());
+        assert!(Block::initial_with(MAX_DIFFICULTY + 1, Vec::<u8>::new()).is_err());
+    }
+
+    #[test]
+    fn verify_link_0() {
+        let b0 = b_mined(8);
+        let mut b1 = Block::next(&b0, String::from("link"));
+        assert!(!b1.verify_link(&b0)); // unmined
+        b1.mine_serial();
+        assert!(b1.verify_link(&b0));
+        assert!(!b0.verify_link(&b1));
+        assert!(!b1.verify_link(&b1));
+
+        // difficulty may go up but not down
+        let mut harder = Block { difficulty: 9, ..Block::next(&b0, String::from("link")) };
+        harder.mine_serial();
+        assert!(harder.verify_link(&b0));
+        let mut easier = Block { difficulty: 7, ..Block::next(&b0, String::from("link")) };
+        easier.mine_serial();
+        assert!(easier.is_valid());
+        assert!(!easier.verify_link(&b0));
+
+        let unmined_prev = Block { proof: None, ..b0.clone() };
+        assert!(!b1.verify_link(&unmined_prev));
+    }
+
+    #[test]
+    fn hash_cached_0() {
+        let b0 = b_mined(8);
+        let h = b0.hash();
+        let mut cached = CachedBlock::new(b0);
+        assert_eq!(cached.hash_cached(), h);
+        assert_eq!(cached.hash_cached(), h);
+        assert!(cached.is_valid_cached());
+
+        // every change goes through the wrapper and drops the cached hash
+        cached.set_proof(cached.block().proof.unwrap() + 1);
+        assert_eq!(cached.hash_cached(), cached.block().hash());
+        assert_ne!(cached.hash_cached(), h);
+
+        let before = cached.hash_cached();
+        cached.block_mut().timestamp += 1;
+        assert_eq!(cached.hash_cached(), cached.block().hash());
+        assert_ne!(cached.hash_cached(), before);
+
+        cached.set_data(String::from("changed"));
+        assert!(!cached.is_valid_cached());
+        cached.block_mut().mine(2).unwrap();
+        assert!(cached.is_valid_cached());
+        assert_eq!(cached.clone().into_block().hash(), cached.hash_cached());
+    }
+
+    #[test]
+    fn leading_difficulty_bytes_0() {
+        let leading = DifficultyMode::Leading;
+        let mut h = [0xffu8; 32];
+        assert!(leading.satisfied_by(0, &h));
+        assert!(!leading.satisfied_by(1, &h));
+        h[0] = 0;
+        assert!(leading.satisfied_by(8, &h));
+        assert!(!leading.satisfied_by(9, &h));
+        h[1] = 0;
+        assert!(leading.satisfied_by(16, &h));
+        // trailing zeros are no help
+        assert!(!leading.satisfied_by(8, &[0xff, 0x00]));
+        assert!(DifficultyMode::Trailing.satisfied_by(8, &[0xff, 0x00]));
+        assert!(!leading.satisfied_by(17, &[0x00, 0x00]));
+    }
+
+    #[test]
+    fn leading_difficulty_bits_0() {
+        let leading = DifficultyMode::Leading;
+        assert!(leading.satisfied_by(1, &[0b0111_1111, 0xff]));
+        assert!(!leading.satisfied_by(1, &[0b1000_0000, 0x00]));
+        assert!(leading.satisfied_by(7, &[0b0000_0001, 0xff]));
+        assert!(!leading.satisfied_by(8, &[0b0000_0001, 0xff]));
+        assert!(leading.satisfied_by(9, &[0x00, 0b0111_1111]));
+        assert!(!leading.satisfied_by(9, &[0x00, 0b1000_0000]));
+        assert!(leading.satisfied_by(12, &[0x00, 0b0000_1111]));
+        assert!(!leading.satisfied_by(12, &[0x00, 0b0001_0000]));
+    }
+
+    #[test]
+    fn leading_difficulty_mine_0() {
+        let mut b0: Block = Block::initial(12);
+        assert_eq!(b0.difficulty_mode, DifficultyMode::Trailing);
+        b0.difficulty_mode = DifficultyMode::Leading;
+        b0.mine(4).unwrap();
+        assert!(b0.is_valid());
+        assert!(hash_to_hex(&b0.hash()).starts_with("000"));
+        assert!(Block::next(&b0, String::new()).difficulty_mode == DifficultyMode::Leading);
+
+        let trailing = Block { difficulty_mode: DifficultyMode::Trailing, ..b0.clone() };
+        // the mode only changes which hashes count, not the hashes themselves
+        assert_eq!(trailing.hash(), b0.hash());
+        assert!(trailing.same_content(&b0));
+    }
+
+    #[test]
+    fn set_data_0() {
+        let mut b0 = b_mined(8);
+        let old_hash = b0.hash();
+        b0.set_data(String::from("new data"));
+        assert_eq!(b0.data, "new data");
+        assert_eq!(b0.proof, None);
+        assert!(!b0.is_valid());
+
+        b0.mine(2).unwrap();
+        assert!(b0.is_valid());
+        assert_ne!(b0.hash(), old_hash);
+    }
+
+    #[test]
+    fn hash_for_proof_digits_0() {
+        // proofs are hashed from a stack buffer, which has to hold the same digits as the hash string
+        use sha2::{Digest, Sha256};
+        let mut b0: Block = Block::initial(8);
+        b0.timestamp = 1_700_000_000;
+        for p in [0, 7, 10, 99, 4321, 1 << 40, u64::MAX] {
+            assert_eq!(b0.hash_for_proof(p), Sha256::digest(b0.hash_string_for_proof(p).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn try_mine_for_proof_0() {
+        let mut b0: Block = Block::initial(8);
+        let proof = b0.try_mine_for_proof(&MiningConfig::new(4)).unwrap();
+        b0.set_proof(proof);
+        assert!(b0.is_valid());
+    }
+
+    #[test]
+    fn try_mine_for_proof_range_exhausted_0() {
+        let cfg = MiningConfig { range: Some(0..16), ..MiningConfig::new(4) };
+        assert_eq!(Block::initial(40).try_mine_for_proof(&cfg), Err(MineError::RangeExhausted { start: 0, end: 16 }));
+    }
+
+    #[test]
+    fn try_mine_for_proof_cancelled_0() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        // difficulty 40 would take hours: only the flag, set from another thread, can end this
+        let cancel = Arc::new(AtomicBool::new(false));
+        let cfg = MiningConfig { cancel: Some(cancel.clone()), ..MiningConfig::new(4) };
+        let setter = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            cancel.store(true, Ordering::Relaxed);
+        });
+        assert_eq!(Block::initial(40).try_mine_for_proof(&cfg), Err(MineError::Cancelled));
+        setter.join().unwrap();
+    }
+
+    #[test]
+    fn try_mine_for_proof_timeout_0() {
+        let cfg = MiningConfig { timeout: Some(std::time::Duration::from_millis(50)), ..MiningConfig::new(4) };
+        assert_eq!(Block::initial(40).try_mine_for_proof(&cfg), Err(MineError::Timeout));
+    }
+
+    #[test]
+    fn task_panic_reraised_0() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        // data that can't be hashed: every mining task panics on its worker. Each search has to
+        // re-raise that here instead of waiting forever for the outputs those tasks never send.
+        #[derive(Debug, Clone)]
+        struct Unhashable;
+        impl AsRef<[u8]> for Unhashable {
+            fn as_ref(&self) -> &[u8] {
+                panic!("unhashable data");
+            }
+        }
+        let b0 = Block::initial_with(8, Unhashable).unwrap();
+        let panics = |search: &dyn Fn()| catch_unwind(AssertUnwindSafe(search)).is_err();
+        assert!(panics(&|| {
+            b0.mine_range(4, 0, 1 << 16, 8);
+        }));
+        assert!(panics(&|| {
+            b0.mine_range_lowest(4, 0, 1 << 16, 8);
+        }));
+        assert!(panics(&|| {
+            b0.mine_range_coordinated(4, 0, 1 << 16, 8);
+        }));
+        assert!(panics(&|| {
+            b0.mine_range_shared(4, 0, 1 << 16);
+        }));
+        assert!(panics(&|| {
+            let _ = Block::mine_chain(&mut [b0.clone()], 4);
+        }));
+        assert!(panics(&|| {
+            Block::par_mine_blocks(&mut [b0.clone()], 4);
+        }));
+        assert!(panics(&|| {
+            let _ = b0.try_mine_for_proof(&MiningConfig::new(4));
+        }));
+    }
+
+    // Block data that records every thread it's hashed on
+    #[derive(Debug, Clone)]
+    struct Traced {
+        data: String,
+        threads: std::sync::Arc<std::sync::Mutex<Vec<std::thread::ThreadId>>>,
+    }
+
+    impl Traced {
+        fn new(data: &str) -> Traced {
+            Traced { data: data.to_string(), threads: Default::default() }
+        }
+
+        // each thread once, in the order they first hashed it
+        fn hashed_on(&self) -> Vec<std::thread::ThreadId> {
+            self.threads.lock().unwrap().clone()
+        }
+    }
+
+    impl AsRef<[u8]> for Traced {
+        fn as_ref(&self) -> &[u8] {
+            let mut threads = self.threads.lock().unwrap();
+            let id = std::thread::current().id();
+            if !threads.contains(&id) {
+                threads.push(id);
+            }
+            self.data.as_bytes()
+        }
+    }
+
+    fn b_mined(difficulty: u8) -> Block {
+        let mut b = Block::initial(difficulty);
+        b.mine_serial();
+        b
     }
 }
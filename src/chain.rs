@@ -0,0 +1,537 @@
+use crate::block::{now_unix_secs, Block, Hash, MineError, Payload, MAX_DIFFICULTY};
+use crate::queue::{Task, WorkQueue};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+
+// What it takes to reconcile two chains: where they part ways and what each has after that
+#[derive(Debug, Clone)]
+pub struct ChainDiff {
+    pub common_prefix: usize,   // number of leading blocks the chains share
+    pub only_self: Vec<Block>,  // self's blocks after the common prefix
+    pub only_other: Vec<Block>, // other's blocks after the common prefix
+}
+
+// The first thing wrong with a chain, as found by Blockchain::verify
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainError {
+    Empty,                                  // no genesis block
+    BadGenesis,                             // first block isn't generation 0 with a zero prev_hash
+    InvalidProof { generation: u64 },       // block's proof doesn't meet its difficulty
+    BrokenLink { generation: u64 },         // block doesn't follow the one before it
+    DifficultyDropped { generation: u64 },  // block's difficulty is too far below the previous one's
+    DifficultyTooLow { generation: u64 },   // append_block: block's difficulty is below the chain's minimum
+    DuplicateGeneration { generation: u64 },  // block's generation was already taken by an earlier block
+    DuplicateData,                          // add_data_unique: same data as the tip
+    Mining(MineError),                      // the new block couldn't be mined
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChainError::Empty => write!(f, "chain has no genesis block"),
+            ChainError::BadGenesis => write!(f, "first block is not a genesis block"),
+            ChainError::InvalidProof { generation } => {
+                write!(f, "block {} has no valid proof", generation)
+            }
+            ChainError::BrokenLink { generation } => {
+                write!(f, "block {} does not follow the block before it", generation)
+            }
+            ChainError::DifficultyDropped { generation } => {
+                write!(f, "difficulty dropped too far at block {}", generation)
+            }
+            ChainError::DifficultyTooLow { generation } => {
+                write!(f, "block {} is below the chain's minimum difficulty", generation)
+            }
+            ChainError::DuplicateGeneration { generation } => {
+                write!(f, "more than one block claims generation {}", generation)
+            }
+            ChainError::DuplicateData => write!(f, "data is the same as the tip's"),
+            ChainError::Mining(e) => write!(f, "mining failed: {}", e),
+        }
+    }
+}
+
+impl From<MineError> for ChainError {
+    fn from(e: MineError) -> ChainError {
+        ChainError::Mining(e)
+    }
+}
+
+impl std::error::Error for ChainError {}
+
+// Called with each block appended to a chain; see on_block_added
+pub type BlockAddedCallback = Arc<dyn Fn(&Block) + Send + Sync>;
+
+// Called with the generation of the first block reorg_to replaced; see on_reorg
+pub type ReorgCallback = Arc<dyn Fn(u64) + Send + Sync>;
+
+#[derive(Clone)]
+pub struct Blockchain {
+    blocks: Vec<Block>,     // blocks[i] has generation i; blocks[0] is the genesis block
+    block_added: Vec<BlockAddedCallback>,   // a clone of the chain keeps calling the same ones
+    reorged: Vec<ReorgCallback>,            // same
+    // (generation, hash) of the last block validate_incremental has checked up to, if any
+    validated_up_to: Option<(u64, Hash)>,
+    min_difficulty: Option<u8>,     // append_block rejects blocks below this; see set_min_difficulty
+}
+
+impl fmt::Debug for Blockchain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Blockchain")
+            .field("blocks", &self.blocks)
+            .field("block_added", &self.block_added.len())
+            .field("reorged", &self.reorged.len())
+            .field("validated_up_to", &self.validated_up_to)
+            .field("min_difficulty", &self.min_difficulty)
+            .finish()
+    }
+}
+
+impl Blockchain {
+    // A chain holding just a mined genesis block
+    pub fn new(difficulty: u8) -> Blockchain {
+        let workers = thread::available_parallelism().map_or(1, |n| n.get());
+        let mut genesis = Block::initial_at(difficulty, now_unix_secs());
+        genesis.mine(workers).expect("no valid proof for the genesis block in the whole u64 proof space");
+        Blockchain {
+            blocks: vec![genesis],
+            block_added: Vec::new(),
+            reorged: Vec::new(),
+            validated_up_to: None,
+            min_difficulty: None,
+        }
+    }
+
+    // Oldest first: blocks()[i] has generation i, and there's always a genesis block
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    // Raw access to the blocks, for tests to tamper with: nothing is checked, so the chain can be
+    // left invalid, or even empty (which tip and push_data panic on)
+    #[cfg(test)]
+    pub(crate) fn blocks_mut(&mut self) -> &mut Vec<Block> {
+        &mut self.blocks
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    // Most recent block
+    pub fn tip(&self) -> &Block {
+        self.blocks.last().expect("a chain always has a genesis block")
+    }
+
+    // The block before the one at `generation`: None for genesis, or if there's no such block
+    pub fn prev_of(&self, generation: u64) -> Option<&Block> {
+        if generation >= self.blocks.len() as u64 {
+            return None;
+        }
+        self.blocks.get(generation.checked_sub(1)? as usize)
+    }
+
+    // The block after the one at `generation`: None for the tip, or if there's no such block
+    pub fn next_of(&self, generation: u64) -> Option<&Block> {
+        self.blocks.get(generation.checked_add(1)? as usize)
+    }
+
+    // Build the block after the tip, mine it with `workers` threads and append it.
+    // Nothing is appended if mining fails.
+    pub fn push_data(&mut self, data: String, workers: usize) -> Result<(), MineError> {
+        let mut b = Block::next_now(self.tip(), data);
+        b.mine(workers)?;
+        self.push_block(b);
+        Ok(())
+    }
+
+    // push_data, unless `data` is exactly the tip's: appending the same thing twice in a row is
+    // usually a bug. push_data itself doesn't check.
+    pub fn add_data_unique(&mut self, data: String, workers: usize) -> Result<(), ChainError> {
+        if data == self.tip().data {
+            return Err(ChainError::DuplicateData);
+        }
+        self.push_data(data, workers)?;
+        Ok(())
+    }
+
+    // push_data for each item in turn, all mined on one pool of `workers` threads instead of a pool
+    // per block (see Block::mine_chain). Gives the new height, i.e. the tip's generation. If a block
+    // can't be mined, the ones before it are still appended and the rest aren't.
+    pub fn append_data_batch(&mut self, items: Vec<String>, workers: usize) -> Result<u64, MineError> {
+        let tip = self.tip();
+        let first = Block::next_now(tip, String::new());
+        // mine_chain points each block at the one before it once that's mined
+        let mut batch: Vec<Block> = items
+            .into_iter()
+            .zip(1..)
+            .map(|(data, i)| Block { generation: tip.generation + i, data, ..first.clone() })
+            .collect();
+        let mined = Block::mine_chain(&mut batch, workers);
+        for b in batch.into_iter().take_while(|b| b.proof.is_some()) {
+            self.push_block(b);
+        }
+        mined?;
+        Ok(self.tip().generation)
+    }
+
+    // Append a block built and mined elsewhere (e.g. received from a peer), if it follows the tip,
+    // isn't below the minimum difficulty (if one is set) and its proof is valid. Nothing is
+    // appended otherwise. A block the chain already has (same hash at the same generation, e.g.
+    // a peer resending the tip) is a no-op success, and doesn't run the on_block_added callbacks.
+    pub fn append_block(&mut self, block: Block) -> Result<(), ChainError> {
+        // same content and proof => same hash, as in diff
+        let present = self.blocks.get(block.generation as usize);
+        if present.is_some_and(|b| b.same_content(&block) && b.proof == block.proof) {
+            return Ok(());
+        }
+        let tip = self.tip();
+        if block.prev_hash != tip.hash() || block.generation != tip.generation + 1 {
+            return Err(ChainError::BrokenLink { generation: block.generation });
+        }
+        if self.min_difficulty.is_some_and(|min| block.difficulty < min) {
+            return Err(ChainError::DifficultyTooLow { generation: block.generation });
+        }
+        if !block.is_valid() {
+            return Err(ChainError::InvalidProof { generation: block.generation });
+        }
+        self.push_block(block);
+        Ok(())
+    }
+
+    // Have append_block reject blocks with a difficulty below `min`, e.g. the genesis block's, so a
+    // peer can't make the chain trivial to extend. None (the default) accepts any difficulty.
+    pub fn set_min_difficulty(&mut self, min: Option<u8>) {
+        self.min_difficulty = min;
+    }
+
+    // Append without checking anything, then tell the on_block_added callbacks
+    fn push_block(&mut self, block: Block) {
+        self.blocks.push(block);
+        for callback in &self.block_added {
+            callback(self.tip());
+        }
+    }
+
+    // Register `callback` to run with every block push_data, append_block or reorg_to appends from now on
+    pub fn on_block_added(&mut self, callback: impl Fn(&Block) + Send + Sync + 'static) {
+        self.block_added.push(Arc::new(callback));
+    }
+
+    // Register `callback` to run whenever reorg_to throws away some of this chain's blocks,
+    // with the generation of the first one (where the two chains forked)
+    pub fn on_reorg(&mut self, callback: impl Fn(u64) + Send + Sync + 'static) {
+        self.reorged.push(Arc::new(callback));
+    }
+
+    // Switch to `other` if it's valid and longer: keep the blocks the two share (see diff) and
+    // replace the rest with other's. Returns whether anything changed. If some of this chain's
+    // blocks were replaced the on_reorg callbacks run first, then on_block_added for each new block.
+    pub fn reorg_to(&mut self, other: &Blockchain) -> bool {
+        if other.len() <= self.len() || !other.is_valid() {
+            return false;
+        }
+        let diff = self.diff(other);
+        self.blocks.truncate(diff.common_prefix);
+        if !diff.only_self.is_empty() {
+            for callback in &self.reorged {
+                callback(diff.common_prefix as u64);
+            }
+        }
+        for b in diff.only_other {
+            self.push_block(b);
+        }
+        true
+    }
+
+    // Every block has a valid proof, the first is a proper genesis block, and each later
+    // block points at the hash of the one before it with the next generation number.
+    // Changing anything in a block breaks its own proof and the next block's prev_hash.
+    pub fn is_valid(&self) -> bool {
+        self.verify(u8::MAX).is_ok()
+    }
+
+    // is_valid, but says what's wrong, and also checks no block's difficulty is more than
+    // `max_drop` below the previous one's (0 => difficulty may never go down).
+    // Everything is checked block by block, so the error is for the earliest bad block.
+    pub fn verify(&self, max_drop: u8) -> Result<(), ChainError> {
+        self.verify_hashing(max_drop, Block::try_hash)
+    }
+
+    // verify, getting each block's hash (None if unmined) from `hash_of`. It's called once per
+    // block: the same hash checks the block's proof and the next block's prev_hash.
+    pub(crate) fn verify_hashing(
+        &self,
+        max_drop: u8,
+        hash_of: impl Fn(&Block) -> Option<Hash>,
+    ) -> Result<(), ChainError> {
+        self.verify_to_tip(max_drop, hash_of).map(|_| ())
+    }
+
+    // verify_hashing, giving the tip's hash if the chain is fine
+    fn verify_to_tip(&self, max_drop: u8, hash_of: impl Fn(&Block) -> Option<Hash>) -> Result<Hash, ChainError> {
+        let genesis = self.blocks.first().ok_or(ChainError::Empty)?;
+        if genesis.generation != 0 || genesis.prev_hash != Hash::default() {
+            return Err(ChainError::BadGenesis);
+        }
+        let genesis_hash = proven_hash(genesis, &hash_of)?;
+        verify_links(&self.blocks, genesis_hash, max_drop, hash_of)
+    }
+
+    // is_valid, but only checking the blocks appended since the last call that succeeded: they
+    // must follow the block it checked up to, which must still be in the chain unchanged (same
+    // hash at the same generation). If it isn't, e.g. after a reorg_to, the whole chain is checked.
+    pub fn validate_incremental(&mut self) -> Result<(), ChainError> {
+        self.validate_incremental_hashing(Block::try_hash)
+    }
+
+    // validate_incremental, getting each block's hash from `hash_of`, as verify_hashing does
+    pub(crate) fn validate_incremental_hashing(
+        &mut self,
+        hash_of: impl Fn(&Block) -> Option<Hash>,
+    ) -> Result<(), ChainError> {
+        let unchanged = |&(generation, hash): &(u64, Hash)| {
+            self.blocks.get(generation as usize).and_then(&hash_of) == Some(hash)
+        };
+        let tip_hash = match self.validated_up_to.filter(unchanged) {
+            Some((generation, hash)) => verify_links(&self.blocks[generation as usize..], hash, u8::MAX, &hash_of)?,
+            None => self.verify_to_tip(u8::MAX, &hash_of)?,
+        };
+        self.validated_up_to = Some((self.tip().generation, tip_hash));
+        Ok(())
+    }
+
+    // Check only that every block's proof meets its difficulty, ignoring how the blocks link up.
+    // Err has the generation of the first block (in chain order) whose proof doesn't.
+    pub fn verify_pow_only(&self) -> Result<(), u64> {
+        verify_pow_only(&self.blocks)
+    }
+
+    // Expected number of hashes it took to mine the whole chain: 2^difficulty per block
+    pub fn total_work(&self) -> u128 {
+        self.blocks
+            .iter()
+            .map(|b| 1u128.checked_shl(b.difficulty as u32).unwrap_or(u128::MAX))
+            .fold(0, u128::saturating_add)
+    }
+
+    // Fork choice: the valid candidate with the most total_work. Ties go to the one with fewer
+    // blocks, then the lowest tip hash, so the same candidates always give the same pick.
+    // None if no candidate is valid.
+    pub fn select_best(candidates: Vec<Blockchain>) -> Option<Blockchain> {
+        candidates
+            .into_iter()
+            .filter(Blockchain::is_valid)
+            .min_by_key(|c| (std::cmp::Reverse(c.total_work()), c.len(), c.tip().hash()))
+    }
+
+    // SHA-256 of every block's hash, concatenated in order: equal for chains with the same
+    // blocks, different (barring a collision) if anything differs. Panics on an unmined block, like hash.
+    pub fn chain_fingerprint(&self) -> Hash {
+        let mut d = Sha256::new();
+        for b in &self.blocks {
+            d.update(b.hash());
+        }
+        d.finalize()
+    }
+
+    // Sum of every block's proof (an unmined one counts as 0): since mining tries proofs from 0
+    // up, a rough measure of the effort that went into the chain
+    pub fn proof_sum(&self) -> u128 {
+        self.blocks.iter().map(|b| b.proof.unwrap_or(0) as u128).sum()
+    }
+
+    // proof_sum per block (0.0 for a chain with no blocks)
+    pub fn average_proof(&self) -> f64 {
+        if self.blocks.is_empty() {
+            return 0.0;
+        }
+        self.proof_sum() as f64 / self.blocks.len() as f64
+    }
+
+    // Generations missing from `blocks` between the lowest and highest present, in order: what a
+    // syncing node still has to ask for. The blocks needn't be sorted, and duplicates are fine.
+    pub fn missing_generations<T: Payload>(blocks: &[Block<T>]) -> Vec<u64> {
+        let mut present: Vec<u64> = blocks.iter().map(|b| b.generation).collect();
+        present.sort_unstable();
+        present.dedup();
+        present.windows(2).flat_map(|pair| pair[0] + 1..pair[1]).collect()
+    }
+
+    // Release spare capacity left behind by pushes and truncation, in the block list and in each
+    // block's data. Doesn't touch anything that goes into a hash, and doesn't share data between
+    // blocks: each block still owns its own String, duplicates included.
+    pub fn compact(&mut self) {
+        self.blocks.shrink_to_fit();
+        for b in &mut self.blocks {
+            b.data.shrink_to_fit();
+        }
+    }
+
+    // Blocks count as shared when their content and proof match, i.e. they have the same hash
+    pub fn diff(&self, other: &Blockchain) -> ChainDiff {
+        let common_prefix = self
+            .blocks
+            .iter()
+            .zip(&other.blocks)
+            .take_while(|(a, b)| a.same_content(b) && a.proof == b.proof)
+            .count();
+        ChainDiff {
+            common_prefix,
+            only_self: self.blocks[common_prefix..].to_vec(),
+            only_other: other.blocks[common_prefix..].to_vec(),
+        }
+    }
+}
+
+// b's hash from `hash_of`, if it meets b's difficulty
+fn proven_hash(b: &Block, hash_of: impl Fn(&Block) -> Option<Hash>) -> Result<Hash, ChainError> {
+    hash_of(b)
+        .filter(|h| b.hash_meets_difficulty(h))
+        .ok_or(ChainError::InvalidProof { generation: b.generation })
+}
+
+// verify_hashing's checks on every block after the first of `blocks`, whose hash is `first_hash`.
+// Gives the hash of the last block.
+fn verify_links(
+    blocks: &[Block],
+    first_hash: Hash,
+    max_drop: u8,
+    hash_of: impl Fn(&Block) -> Option<Hash>,
+) -> Result<Hash, ChainError> {
+    let mut prev_hash = first_hash;
+    for pair in blocks.windows(2) {
+        let (prev, b) = (&pair[0], &pair[1]);
+        // the blocks so far count up one at a time, so an earlier one already has any generation
+        // up to prev's
+        if b.generation <= prev.generation {
+            return Err(ChainError::DuplicateGeneration { generation: b.generation });
+        }
+        if b.prev_hash != prev_hash || b.generation != prev.generation + 1 {
+            return Err(ChainError::BrokenLink { generation: b.generation });
+        }
+        if b.difficulty < prev.difficulty.saturating_sub(max_drop) {
+            return Err(ChainError::DifficultyDropped { generation: b.generation });
+        }
+        prev_hash = proven_hash(b, &hash_of)?;
+    }
+    Ok(prev_hash)
+}
+
+// Blockchain::verify_pow_only for any set of blocks, in any order (e.g. shuffled, or with gaps)
+pub fn verify_pow_only<T: Payload>(blocks: &[Block<T>]) -> Result<(), u64> {
+    match blocks.iter().find(|b| !b.is_valid()) {
+        Some(b) => Err(b.generation),
+        None => Ok(()),
+    }
+}
+
+// How many of the most recent blocks retarget averages over
+pub const RETARGET_WINDOW: usize = 10;
+
+// Difficulty for the block after `previous_blocks`, from how fast the last RETARGET_WINDOW of them
+// came: one more than the latest block's if they came faster than one per `target_secs` on
+// average, one less if slower, the same if spot on (or if there's only one block to go on).
+// Always within 0..=MAX_DIFFICULTY, the difficulties Block::next_with_difficulty accepts.
+// With no blocks at all there's no difficulty to adjust: 0, the easiest.
+pub fn retarget<T: Payload>(previous_blocks: &[Block<T>], target_secs: u64) -> u8 {
+    let latest = match previous_blocks.last() {
+        Some(b) => b,
+        None => return 0,
+    };
+    let recent = &previous_blocks[previous_blocks.len().saturating_sub(RETARGET_WINDOW)..];
+    let difficulty = if recent.len() < 2 {
+        latest.difficulty
+    } else {
+        // compare total time against the target total instead of averaging, so nothing is rounded
+        let elapsed = latest.timestamp.saturating_sub(recent[0].timestamp);
+        let target = target_secs.saturating_mul(recent.len() as u64 - 1);
+        if elapsed < target {
+            latest.difficulty.saturating_add(1)
+        } else if elapsed > target {
+            latest.difficulty.saturating_sub(1)
+        } else {
+            latest.difficulty
+        }
+    };
+    difficulty.min(MAX_DIFFICULTY)
+}
+
+// Blockchain::is_valid with the hashing spread over `workers` threads: each block's hash is
+// recomputed (and checked against its difficulty) on the pool, then the links are checked here
+// using those hashes. Gives up as soon as any block's proof turns out to be bad.
+pub fn verify_chain_parallel(blocks: &[Block], workers: usize) -> bool {
+    let genesis = match blocks.first() {
+        Some(b) => b,
+        None => return false,
+    };
+    if genesis.generation != 0 || genesis.prev_hash != Hash::default() {
+        return false;
+    }
+
+    // no workers would mean nobody hashes and recv waits forever: use at least one
+    let workers = workers.max(1);
+    let shared = Arc::new(blocks.to_vec());
+    // a few chunks per worker: default_chunks keeps chunks to MIN_CHUNK_SIZE hashes, which would
+    // leave any chain shorter than a couple thousand blocks as one chunk on one worker
+    let chunk_size = blocks.len().div_ceil(4 * workers);
+    let mut queue = WorkQueue::new(workers);
+    let tasks_submitted = queue
+        .enqueue_all((0..blocks.len()).step_by(chunk_size).map(|start| HashCheckTask {
+            blocks: shared.clone(),
+            start,
+            end: (start + chunk_size).min(blocks.len()),
+        }))
+        .expect("a fresh queue accepts tasks");
+
+    let mut hashes = vec![Hash::default(); blocks.len()];
+    // a chunk that panics produces no output: iter_n stops once every chunk has settled instead
+    // of waiting for it, and the chain counts as invalid
+    let mut hashed = 0;
+    for output in queue.iter_n(tasks_submitted) {
+        match output {
+            (start, Some(chunk_hashes)) => {
+                hashes[start..start + chunk_hashes.len()].copy_from_slice(&chunk_hashes);
+                hashed += 1;
+            }
+            (_, None) => break,
+        }
+    }
+    if hashed < tasks_submitted {
+        // dropping the queue shuts it down; don't start the chunks still queued
+        queue.cancel();
+        return false;
+    }
+
+    blocks.windows(2).zip(&hashes).all(|(pair, prev_hash)| {
+        let (prev, b) = (&pair[0], &pair[1]);
+        b.prev_hash == *prev_hash && b.generation == prev.generation + 1
+    })
+}
+
+// Hashes blocks[start..end] for verify_chain_parallel: (start, their hashes), or (start, None)
+// if one of them doesn't have a valid proof
+#[derive(Debug)]
+struct HashCheckTask {
+    blocks: Arc<Vec<Block>>,
+    start: usize,
+    end: usize,
+}
+
+impl Task for HashCheckTask {
+    type Output = (usize, Option<Vec<Hash>>);
+
+    fn run(&self) -> Option<Self::Output> {
+        let hashes = self.blocks[self.start..self.end]
+            .iter()
+            .map(|b| b.try_hash().filter(|h| b.hash_meets_difficulty(h)))
+            .collect();
+        Some((self.start, hashes))
+    }
+}
@@ -0,0 +1,616 @@
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod chain_tests {
+    use crate::block::*;
+    use crate::chain::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    fn chain_of(n: usize) -> Blockchain {
+        let mut c = Blockchain::new(8);
+        for i in 1..n {
+            c.push_data(format!("block {}", i), 4).unwrap();
+        }
+        c
+    }
+
+    #[test]
+    fn new_basic_0() {
+        let c = Blockchain::new(8);
+        assert_eq!(c.len(), 1);
+        assert_eq!(c.tip().generation, 0);
+        assert_eq!(c.tip().prev_hash, Hash::default());
+        assert!(c.tip().is_valid());
+        assert!(c.is_valid());
+    }
+
+    #[test]
+    fn push_data_0() {
+        let c = chain_of(4);
+        assert_eq!(c.len(), 4);
+        assert_eq!(c.tip().generation, 3);
+        assert_eq!(c.tip().data, "block 3");
+        assert_eq!(c.tip().prev_hash, c.blocks()[2].hash());
+        assert!(c.is_valid());
+    }
+
+    #[test]
+    fn tampered_data_0() {
+        let mut c = chain_of(4);
+        c.blocks_mut()[1].data = "forged".to_string();
+        assert!(!c.is_valid());
+    }
+
+    #[test]
+    fn tampered_and_remined_0() {
+        // re-mining the tampered block fixes its proof, but the next block still points at the old hash
+        let mut c = chain_of(4);
+        c.blocks_mut()[1].data = "forged".to_string();
+        c.blocks_mut()[1].mine(4).unwrap();
+        assert!(c.blocks()[1].is_valid());
+        assert!(!c.is_valid());
+    }
+
+    #[test]
+    fn bad_generation_0() {
+        let mut c = chain_of(3);
+        let mut b = Block::next(c.tip(), "skip".to_string());
+        b.generation += 1;
+        b.mine(4).unwrap();
+        c.blocks_mut().push(b);
+        assert!(!c.is_valid());
+    }
+
+    #[test]
+    fn bad_genesis_0() {
+        let mut c = Blockchain::new(8);
+        c.blocks_mut()[0].prev_hash = Hash::from([1; 32]);
+        c.blocks_mut()[0].mine(4).unwrap();
+        assert!(!c.is_valid());
+
+        let mut empty = Blockchain::new(0);
+        empty.blocks_mut().clear();
+        assert!(!empty.is_valid());
+    }
+
+    #[test]
+    fn diff_0() {
+        let mut c0 = chain_of(3);
+        let mut c1 = c0.clone();
+        c0.push_data("mine".to_string(), 4).unwrap();
+        c1.push_data("theirs".to_string(), 4).unwrap();
+        c1.push_data("theirs again".to_string(), 4).unwrap();
+
+        let d = c0.diff(&c1);
+        assert_eq!(d.common_prefix, 3);
+        assert_eq!(d.only_self.len(), 1);
+        assert_eq!(d.only_self[0].data, "mine");
+        let other_data: Vec<&str> = d.only_other.iter().map(|b| b.data.as_str()).collect();
+        assert_eq!(other_data, vec!["theirs", "theirs again"]);
+    }
+
+    #[test]
+    fn diff_prefix_0() {
+        // one chain simply extends the other
+        let c0 = chain_of(2);
+        let mut c1 = c0.clone();
+        c1.push_data("more".to_string(), 4).unwrap();
+
+        let d = c0.diff(&c1);
+        assert_eq!(d.common_prefix, 2);
+        assert!(d.only_self.is_empty());
+        assert_eq!(d.only_other.len(), 1);
+
+        let d = c0.diff(&c0);
+        assert_eq!(d.common_prefix, 2);
+        assert!(d.only_self.is_empty() && d.only_other.is_empty());
+    }
+
+    #[test]
+    fn compact_0() {
+        let mut c = chain_of(3);
+        c.blocks_mut().reserve(100);
+        let mut padded = String::with_capacity(1000);
+        padded.push_str("block 1");
+        c.blocks_mut()[1].data = padded;
+        let before = c.clone();
+
+        c.compact();
+        assert_eq!(c.blocks_mut().capacity(), c.len());
+        assert_eq!(c.blocks()[1].data.capacity(), "block 1".len());
+        // nothing that is hashed changed
+        assert_eq!(c.diff(&before).common_prefix, 3);
+        assert!(c.is_valid());
+    }
+
+    #[test]
+    fn on_block_added_0() {
+        let mut c = Blockchain::new(8);
+        let added = Arc::new(AtomicU64::new(0));
+        let last_generation = Arc::new(AtomicU64::new(0));
+        let (a, g) = (added.clone(), last_generation.clone());
+        c.on_block_added(move |b| {
+            a.fetch_add(1, Ordering::SeqCst);
+            g.store(b.generation, Ordering::SeqCst);
+        });
+
+        for i in 0..3 {
+            c.push_data(format!("block {}", i), 4).unwrap();
+        }
+        assert_eq!(added.load(Ordering::SeqCst), 3);
+        assert_eq!(last_generation.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn on_reorg_0() {
+        // both chains share genesis and block 1; the other one then goes on for three more
+        let mut c = chain_of(3);
+        let mut other = Blockchain::new(8);
+        *other.blocks_mut() = c.blocks()[..2].to_vec();
+        for i in 0..3 {
+            other.push_data(format!("other {}", i), 4).unwrap();
+        }
+
+        let forks = Arc::new(AtomicU64::new(0));
+        let fork_generation = Arc::new(AtomicU64::new(0));
+        let added = Arc::new(AtomicU64::new(0));
+        let (f, g, a) = (forks.clone(), fork_generation.clone(), added.clone());
+        c.on_reorg(move |generation| {
+            f.fetch_add(1, Ordering::SeqCst);
+            g.store(generation, Ordering::SeqCst);
+        });
+        c.on_block_added(move |_| {
+            a.fetch_add(1, Ordering::SeqCst);
+        });
+
+        assert!(c.reorg_to(&other));
+        assert_eq!(forks.load(Ordering::SeqCst), 1);
+        assert_eq!(fork_generation.load(Ordering::SeqCst), 2);
+        assert_eq!(added.load(Ordering::SeqCst), 3);
+        assert_eq!(c.len(), 5);
+        assert_eq!(c.diff(&other).common_prefix, 5);
+        assert!(c.is_valid());
+
+        // not longer (or just extending it) isn't a reorg
+        assert!(!c.reorg_to(&other));
+        other.push_data(String::from("more"), 4).unwrap();
+        assert!(c.reorg_to(&other));
+        assert_eq!(forks.load(Ordering::SeqCst), 1);
+        assert_eq!(added.load(Ordering::SeqCst), 4);
+
+        // nor is switching to a broken chain
+        let mut broken = other.clone();
+        broken.push_data(String::from("last"), 4).unwrap();
+        broken.blocks_mut()[3].data = String::from("tampered");
+        assert!(!c.reorg_to(&broken));
+        assert_eq!(c.len(), 6);
+    }
+
+    #[test]
+    fn verify_difficulty_dropped_0() {
+        // difficulty 8 (genesis and block 1), then 7, 7, a sharp drop to 2, and back up to 9
+        let mut c = chain_of(2);
+        for (i, difficulty) in [(2, 7), (3, 7), (4, 2), (5, 9)] {
+            let mut b = Block::next(c.tip(), format!("block {}", i));
+            b.difficulty = difficulty;
+            b.mine(4).unwrap();
+            c.blocks_mut().push(b);
+        }
+
+        assert!(c.is_valid());
+        assert_eq!(c.verify(u8::MAX), Ok(()));
+        assert_eq!(c.verify(6), Ok(()));
+        assert_eq!(c.verify(2), Err(ChainError::DifficultyDropped { generation: 4 }));
+        assert_eq!(c.verify(1), Err(ChainError::DifficultyDropped { generation: 4 }));
+        assert_eq!(c.verify(0), Err(ChainError::DifficultyDropped { generation: 2 }));
+    }
+
+    #[test]
+    fn verify_errors_0() {
+        let mut empty = Blockchain::new(0);
+        empty.blocks_mut().clear();
+        assert_eq!(empty.verify(0), Err(ChainError::Empty));
+
+        let mut c = chain_of(3);
+        c.blocks_mut()[2].proof = None;
+        assert_eq!(c.verify(0), Err(ChainError::InvalidProof { generation: 2 }));
+        c.blocks_mut()[1].generation = 5;
+        assert_eq!(c.verify(0), Err(ChainError::BrokenLink { generation: 5 }));
+    }
+
+    #[test]
+    fn verify_pow_only_0() {
+        let mut c = chain_of(5);
+        assert_eq!(c.verify_pow_only(), Ok(()));
+        c.blocks_mut()[3].proof = None;
+        assert_eq!(c.verify_pow_only(), Err(3));
+
+        // links don't matter, nor does the order
+        let mut shuffled = c.blocks().to_vec();
+        shuffled[3].mine(4).unwrap();
+        shuffled.swap(0, 4);
+        shuffled.remove(2);
+        assert_eq!(verify_pow_only(&shuffled), Ok(()));
+        assert!(!verify_chain_parallel(&shuffled, 4));
+        shuffled[1].proof = None;
+        shuffled[0].proof = None;
+        assert_eq!(verify_pow_only(&shuffled), Err(4));
+        assert_eq!(verify_pow_only::<String>(&[]), Ok(()));
+    }
+
+    #[test]
+    fn missing_generations_0() {
+        let blocks: Vec<Block> = [0, 1, 3, 4]
+            .iter()
+            .map(|&generation| Block { generation, ..Block::initial(0) })
+            .collect();
+        assert_eq!(Blockchain::missing_generations(&blocks), vec![2]);
+
+        let gappy: Vec<Block> = [9, 2, 5, 5, 3]
+            .iter()
+            .map(|&generation| Block { generation, ..Block::initial(0) })
+            .collect();
+        assert_eq!(Blockchain::missing_generations(&gappy), vec![4, 6, 7, 8]);
+        assert_eq!(Blockchain::missing_generations(chain_of(4).blocks()), Vec::<u64>::new());
+        assert_eq!(Blockchain::missing_generations::<String>(&[]), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn verify_hashes_once_0() {
+        // one hash per block, where checking each link with prev.hash() would take two
+        use std::cell::Cell;
+        let mut c = chain_of(10);
+        let hashes = Cell::new(0);
+        let counted = |b: &Block| {
+            hashes.set(hashes.get() + 1);
+            b.try_hash()
+        };
+        assert_eq!(c.verify_hashing(0, counted), Ok(()));
+        assert_eq!(hashes.get(), c.len());
+
+        // same results as before for a broken chain, stopping at the first bad block
+        c.blocks_mut()[4].set_data(String::from("forged"));
+        hashes.set(0);
+        assert_eq!(c.verify_hashing(0, counted), Err(ChainError::InvalidProof { generation: 4 }));
+        assert_eq!(c.verify(0), Err(ChainError::InvalidProof { generation: 4 }));
+        assert_eq!(hashes.get(), 5);
+        c.blocks_mut()[4].mine(4).unwrap();
+        assert_eq!(c.verify(0), Err(ChainError::BrokenLink { generation: 5 }));
+    }
+
+    #[test]
+    fn verify_chain_parallel_0() {
+        let mut c = chain_of(12);
+        assert!(verify_chain_parallel(c.blocks(), 4));
+        assert!(verify_chain_parallel(c.blocks(), 1));
+        assert!(verify_chain_parallel(c.blocks(), 0));
+        assert!(!verify_chain_parallel(&[], 4));
+
+        // bad proof, somewhere past the first chunk: 12 blocks over 2 workers are chunks of 2,
+        // over 4 workers chunks of 1
+        let proof = c.blocks()[9].proof;
+        c.blocks_mut()[9].proof = None;
+        assert!(!verify_chain_parallel(c.blocks(), 2));
+        assert!(!verify_chain_parallel(c.blocks(), 4));
+        c.blocks_mut()[9].proof = proof;
+
+        // valid proofs, broken link
+        c.blocks_mut()[11].prev_hash = c.blocks()[9].hash();
+        c.blocks_mut()[11].mine(4).unwrap();
+        assert!(c.blocks().iter().all(|b| b.is_valid()));
+        assert!(!verify_chain_parallel(c.blocks(), 4));
+        assert_eq!(verify_chain_parallel(c.blocks(), 4), c.is_valid());
+    }
+
+    // n blocks at `difficulty`, `gap` seconds apart. Their proof is a placeholder 0 (rarely valid)
+    // only so next_at can hash each one to link the next.
+    fn timed_blocks(n: usize, difficulty: u8, gap: u64) -> Vec<Block> {
+        let mut blocks = vec![Block::initial(difficulty)];
+        blocks[0].timestamp = 1_700_000_000;
+        blocks[0].proof = Some(0);
+        for i in 1..n {
+            let prev = &blocks[i - 1];
+            let mut b = Block::next_at(prev, format!("block {}", i), prev.timestamp + gap);
+            b.proof = Some(0);
+            blocks.push(b);
+        }
+        blocks
+    }
+
+    #[test]
+    fn retarget_0() {
+        // fast blocks => harder, slow => easier, on target => unchanged
+        assert_eq!(retarget(&timed_blocks(20, 10, 2), 10), 11);
+        assert_eq!(retarget(&timed_blocks(20, 10, 30), 10), 9);
+        assert_eq!(retarget(&timed_blocks(20, 10, 10), 10), 10);
+        assert_eq!(retarget(&timed_blocks(1, 10, 2), 10), 10);
+
+        // only the last RETARGET_WINDOW blocks count
+        let mut blocks = timed_blocks(5, 10, 1000);
+        blocks.extend(timed_blocks(RETARGET_WINDOW, 10, 2).into_iter().map(|mut b| {
+            b.timestamp += 10_000;
+            b
+        }));
+        assert_eq!(retarget(&blocks, 10), 11);
+
+        // clamped
+        assert_eq!(retarget(&timed_blocks(3, MAX_DIFFICULTY, 1), 10), MAX_DIFFICULTY);
+        assert_eq!(retarget(&timed_blocks(3, 1, 100), 10), 0);
+        assert_eq!(retarget(&timed_blocks(3, 0, 100), 10), 0);
+        assert_eq!(retarget(&timed_blocks(3, 0, 10), 10), 0);
+        // the same clamp with too few blocks to go on, or when they came fast
+        let mut over = timed_blocks(3, 10, 1);
+        for b in &mut over {
+            b.difficulty = MAX_DIFFICULTY + 5;
+        }
+        assert_eq!(retarget(&over[..1], 10), MAX_DIFFICULTY);
+        assert_eq!(retarget(&over, 10), MAX_DIFFICULTY);
+
+        // no blocks, nothing to go on
+        assert_eq!(retarget::<String>(&[], 10), 0);
+
+        // the next block can take the new difficulty
+        let blocks = timed_blocks(20, 10, 2);
+        let difficulty = retarget(&blocks, 10);
+        let next = Block::next_with_difficulty(blocks.last().unwrap(), String::from("next"), difficulty).unwrap();
+        assert_eq!(next.difficulty, 11);
+        assert_eq!(next.prev_hash, blocks.last().unwrap().hash());
+        assert_eq!(
+            Block::next_with_difficulty(&next, String::new(), MAX_DIFFICULTY + 1).unwrap_err(),
+            InvalidDifficulty(MAX_DIFFICULTY + 1)
+        );
+    }
+
+    #[test]
+    fn append_block_0() {
+        let mut c = Blockchain::new(8);
+        let added = Arc::new(AtomicU64::new(0));
+        let a = added.clone();
+        c.on_block_added(move |_| {
+            a.fetch_add(1, Ordering::SeqCst);
+        });
+
+        for i in 0..3 {
+            let mut b = Block::next(c.tip(), format!("block {}", i));
+            b.mine(4).unwrap();
+            c.append_block(b).unwrap();
+        }
+        assert_eq!(added.load(Ordering::SeqCst), 3);
+        assert_eq!(c.len(), 4);
+        assert!(c.is_valid());
+
+        // not following the tip, or unmined: rejected, and no callback
+        let mut stale = Block::next(&c.blocks()[1], String::from("stale"));
+        stale.mine(4).unwrap();
+        assert_eq!(c.append_block(stale), Err(ChainError::BrokenLink { generation: 2 }));
+        let unmined = Block::next(c.tip(), String::from("unmined"));
+        assert_eq!(c.append_block(unmined), Err(ChainError::InvalidProof { generation: 4 }));
+        assert_eq!(added.load(Ordering::SeqCst), 3);
+        assert_eq!(c.len(), 4);
+    }
+
+    #[test]
+    fn append_data_batch_0() {
+        let mut c = chain_of(2);
+        let added = Arc::new(AtomicU64::new(0));
+        let a = added.clone();
+        c.on_block_added(move |_| {
+            a.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let items = (0..5).map(|i| format!("item {}", i)).collect();
+        assert_eq!(c.append_data_batch(items, 4), Ok(6));
+        assert_eq!(c.len(), 7);
+        assert_eq!(c.tip().data, "item 4");
+        assert_eq!(added.load(Ordering::SeqCst), 5);
+        assert!(c.is_valid());
+
+        assert_eq!(c.append_data_batch(Vec::new(), 4), Ok(6));
+    }
+
+    #[test]
+    fn validate_incremental_0() {
+        use std::cell::Cell;
+        let mut c = chain_of(5);
+        let hashes = Cell::new(0);
+        let counted = |b: &Block| {
+            hashes.set(hashes.get() + 1);
+            b.try_hash()
+        };
+        // first time round, everything
+        assert_eq!(c.validate_incremental_hashing(counted), Ok(()));
+        assert_eq!(hashes.get(), 5);
+
+        // then only the new blocks, plus the last one checked to make sure it's still there
+        c.push_data(String::from("five"), 4).unwrap();
+        c.push_data(String::from("six"), 4).unwrap();
+        hashes.set(0);
+        assert_eq!(c.validate_incremental_hashing(counted), Ok(()));
+        assert_eq!(hashes.get(), 3);
+        hashes.set(0);
+        assert_eq!(c.validate_incremental_hashing(counted), Ok(()));
+        assert_eq!(hashes.get(), 1);
+
+        // a new block that doesn't link up is caught
+        let mut stray = Block::next(&c.blocks()[2], String::from("stray"));
+        stray.generation = 7;
+        stray.mine(4).unwrap();
+        c.blocks_mut().push(stray);
+        assert_eq!(c.validate_incremental(), Err(ChainError::BrokenLink { generation: 7 }));
+        c.blocks_mut().pop();
+
+        // the block it checked up to changed => the whole chain again
+        c.blocks_mut()[6].set_data(String::from("forged"));
+        c.blocks_mut()[6].mine(4).unwrap();
+        hashes.set(0);
+        assert_eq!(c.validate_incremental_hashing(counted), Ok(()));
+        assert_eq!(hashes.get(), 1 + 7);
+        c.blocks_mut()[3].set_data(String::from("forged"));
+        c.blocks_mut().truncate(5);
+        assert_eq!(c.validate_incremental(), Err(ChainError::InvalidProof { generation: 3 }));
+    }
+
+    #[test]
+    fn append_block_min_difficulty_0() {
+        let mut c = chain_of(2);
+        let mut unguarded = c.clone();
+        let mut easy = Block::next_with_difficulty(c.tip(), String::from("easy"), 4).unwrap();
+        easy.mine(4).unwrap();
+
+        // held to the genesis block's difficulty
+        c.set_min_difficulty(Some(c.blocks()[0].difficulty));
+        assert_eq!(c.append_block(easy.clone()), Err(ChainError::DifficultyTooLow { generation: 2 }));
+        assert_eq!(c.len(), 2);
+        let mut fine = Block::next(c.tip(), String::from("fine"));
+        fine.mine(4).unwrap();
+        assert_eq!(c.append_block(fine), Ok(()));
+
+        // no minimum (the default): anything with a valid proof goes
+        assert_eq!(unguarded.append_block(easy), Ok(()));
+    }
+
+    #[test]
+    fn proof_sum_0() {
+        let mut c = chain_of(4);
+        for (b, proof) in c.blocks_mut().iter_mut().zip([10, 20, 30, 40]) {
+            b.proof = Some(proof);
+        }
+        assert_eq!(c.proof_sum(), 100);
+        assert_eq!(c.average_proof(), 25.0);
+
+        // no overflow summing proofs near u64::MAX
+        for b in c.blocks_mut() {
+            b.proof = Some(u64::MAX);
+        }
+        assert_eq!(c.proof_sum(), 4 * u64::MAX as u128);
+
+        c.blocks_mut().clear();
+        assert_eq!(c.proof_sum(), 0);
+        assert_eq!(c.average_proof(), 0.0);
+    }
+
+    #[test]
+    fn verify_duplicate_generation_0() {
+        // a second generation 2, properly mined on top of block 1
+        let mut c = chain_of(3);
+        let mut again = Block::next(&c.blocks()[1], String::from("block 2 again"));
+        again.mine(4).unwrap();
+        c.blocks_mut().push(again);
+        assert_eq!(c.verify(u8::MAX), Err(ChainError::DuplicateGeneration { generation: 2 }));
+        assert!(!c.is_valid());
+        assert_eq!(c.validate_incremental(), Err(ChainError::DuplicateGeneration { generation: 2 }));
+
+        // and one going backwards
+        let mut c = chain_of(4);
+        let genesis = c.blocks()[0].clone();
+        c.blocks_mut().push(genesis);
+        assert_eq!(c.verify(u8::MAX), Err(ChainError::DuplicateGeneration { generation: 0 }));
+    }
+
+    #[test]
+    fn chain_fingerprint_0() {
+        use sha2::{Digest, Sha256};
+        let c0 = chain_of(4);
+        let c1 = c0.clone();
+        assert_eq!(c0.chain_fingerprint(), c1.chain_fingerprint());
+        let concatenated: Vec<u8> = c0.blocks().iter().flat_map(|b| b.hash()).collect();
+        assert_eq!(c0.chain_fingerprint(), Sha256::digest(&concatenated));
+
+        // tampered and re-mined: each block still has a valid proof, but the fingerprint moves
+        let mut tampered = c0.clone();
+        tampered.blocks_mut()[2].set_data(String::from("forged"));
+        tampered.blocks_mut()[2].mine(4).unwrap();
+        assert_ne!(tampered.chain_fingerprint(), c0.chain_fingerprint());
+
+        // as does one block more
+        let mut longer = c0.clone();
+        longer.push_data(String::from("more"), 4).unwrap();
+        assert_ne!(longer.chain_fingerprint(), c0.chain_fingerprint());
+    }
+
+    #[test]
+    fn add_data_unique_0() {
+        let mut c = Blockchain::new(8);
+        c.add_data_unique(String::from("a"), 4).unwrap();
+        assert_eq!(c.add_data_unique(String::from("a"), 4), Err(ChainError::DuplicateData));
+        assert_eq!(c.len(), 2);
+
+        // only the tip counts, and push_data doesn't check at all
+        c.add_data_unique(String::from("b"), 4).unwrap();
+        c.add_data_unique(String::from("a"), 4).unwrap();
+        c.push_data(String::from("a"), 4).unwrap();
+        assert_eq!(c.len(), 5);
+        assert!(c.is_valid());
+    }
+
+    #[test]
+    fn prev_of_next_of_0() {
+        let c = chain_of(4);
+        assert_eq!(c.prev_of(0).map(|b| b.generation), None);
+        assert_eq!(c.prev_of(2).map(|b| b.generation), Some(1));
+        assert_eq!(c.prev_of(3).map(|b| b.generation), Some(2));
+        assert_eq!(c.next_of(0).map(|b| b.generation), Some(1));
+        assert_eq!(c.next_of(2).map(|b| b.generation), Some(3));
+        assert_eq!(c.next_of(3).map(|b| b.generation), None);
+
+        // generations past the tip have no neighbours
+        assert_eq!(c.prev_of(4).map(|b| b.generation), None);
+        assert_eq!(c.next_of(4).map(|b| b.generation), None);
+        assert_eq!(c.next_of(u64::MAX).map(|b| b.generation), None);
+    }
+
+    #[test]
+    fn append_block_again_0() {
+        let mut c = chain_of(3);
+        let added = Arc::new(AtomicU64::new(0));
+        let a = added.clone();
+        c.on_block_added(move |_| {
+            a.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // the tip, or any other block already in the chain: fine, and nothing changes
+        let tip = c.tip().clone();
+        assert_eq!(c.append_block(tip), Ok(()));
+        let middle = c.blocks()[1].clone();
+        assert_eq!(c.append_block(middle), Ok(()));
+        assert_eq!(c.len(), 3);
+        assert_eq!(added.load(Ordering::SeqCst), 0);
+
+        // a different block at an existing generation still doesn't link
+        let mut rival = Block::next(&c.blocks()[1], String::from("rival"));
+        rival.mine(4).unwrap();
+        assert_eq!(c.append_block(rival), Err(ChainError::BrokenLink { generation: 2 }));
+
+        // and a new one still goes on the end
+        let mut b = Block::next(c.tip(), String::from("new"));
+        b.mine(4).unwrap();
+        assert_eq!(c.append_block(b), Ok(()));
+        assert_eq!(c.len(), 4);
+        assert_eq!(added.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn select_best_0() {
+        let light = chain_of(3);
+        let mut heavy = Blockchain::new(10);
+        heavy.push_data(String::from("one"), 4).unwrap();
+        let mut broken = chain_of(10);
+        broken.blocks_mut()[2].set_data(String::from("forged"));
+        assert_eq!(light.total_work(), 3 * 256);
+        assert_eq!(heavy.total_work(), 2 * 1024);
+        assert!(broken.total_work() > heavy.total_work());
+
+        // the broken chain has the most work, and the light one the most blocks: heavy still wins
+        let best = Blockchain::select_best(vec![light.clone(), broken.clone(), heavy.clone()]).unwrap();
+        assert_eq!(best.chain_fingerprint(), heavy.chain_fingerprint());
+
+        // equal work: fewer blocks wins
+        let mut short = Blockchain::new(9);
+        short.push_data(String::from("one"), 4).unwrap();
+        let best = Blockchain::select_best(vec![chain_of(4), short.clone()]).unwrap();
+        assert_eq!(best.chain_fingerprint(), short.chain_fingerprint());
+
+        assert!(Blockchain::select_best(vec![broken]).is_none());
+        assert!(Blockchain::select_best(Vec::new()).is_none());
+    }
+}
@@ -1,4 +1,6 @@
 pub mod block;
 mod block_tests;
+pub mod chain;
+mod chain_tests;
 pub mod queue;
 mod queue_tests;
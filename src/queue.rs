@@ -1,20 +1,185 @@
-use std::sync::mpsc;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashSet, VecDeque};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
 use std::thread;
-
-use digest::Output;
+use std::time::{Duration, Instant};
 
 pub trait Task {
     type Output: Send;
     fn run(&self) -> Option<Self::Output>;  // create output
     // if "run" gives Some output => mpsc channel in main thread, otherwise (gives None output), it should be ignored
+
+    // What the workers actually call. Long-running tasks override this to poll `should_continue`
+    // now and then and give up (return None) once it's false, i.e. once the queue is cancelled.
+    fn run_cancellable(&self, should_continue: &dyn Fn() -> bool) -> Option<Self::Output> {
+        let _ = should_continue;
+        self.run()
+    }
+
+    // Size of this task in whatever unit suits it (e.g. proofs to check), for progress reporting
+    fn total_work(&self) -> u64 {
+        1
+    }
+}
+
+thread_local! {
+    static WORKER_ID: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+// Index (0..n_workers) of the queue worker running on this thread; None outside a worker
+pub fn current_worker() -> Option<usize> {
+    WORKER_ID.with(|id| id.get())
+}
+
+// Wraps a task so its output is tagged with the index of the worker that produced it
+#[derive(Debug)]
+pub struct Attributed<T>(pub T);
+
+impl<T: Task> Task for Attributed<T> {
+    type Output = (usize, T::Output);
+
+    fn run(&self) -> Option<Self::Output> {
+        self.run_cancellable(&|| true)
+    }
+
+    fn run_cancellable(&self, should_continue: &dyn Fn() -> bool) -> Option<Self::Output> {
+        let worker = current_worker().expect("Attributed tasks only run on WorkQueue workers");
+        self.0.run_cancellable(should_continue).map(|output| (worker, output))
+    }
+
+    fn total_work(&self) -> u64 {
+        self.0.total_work()
+    }
+}
+
+// Run `f` on every input across `n_workers` threads, for one-off jobs that don't deserve their own
+// Task type. Gives the Some outputs in input order; inputs where `f` gives None (or panics) are left out.
+pub fn par_map<I, O, F>(n_workers: usize, inputs: impl IntoIterator<Item = I>, f: F) -> Vec<O>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+    F: Fn(I) -> Option<O> + Send + Sync + 'static,
+{
+    let f = Arc::new(f);
+    let mut queue = WorkQueue::new(n_workers);
+    let tasks = inputs.into_iter().enumerate().map(|(index, input)| MapTask {
+        index,
+        input: Cell::new(Some(input)),
+        f: f.clone(),
+        output: PhantomData,
+    });
+    let tasks_submitted = match queue.enqueue_all(tasks) {
+        Ok(n) => n,
+        Err(_) => panic!("a fresh queue accepts tasks"),
+    };
+    // every task reports, but a panicking one doesn't: iter_n won't wait for it forever
+    let mut outputs: Vec<(usize, O)> = queue
+        .iter_n(tasks_submitted)
+        .filter_map(|(index, output)| output.map(|o| (index, o)))
+        .collect();
+    outputs.sort_unstable_by_key(|&(index, _)| index);
+    outputs.into_iter().map(|(_, o)| o).collect()
+}
+
+// One par_map input: always reports, tagged with where the input was, so par_map knows when it's
+// done and can put the outputs back in order
+struct MapTask<I, O, F> {
+    index: usize,
+    input: Cell<Option<I>>,     // run takes &self, so the input is moved out of here
+    f: Arc<F>,
+    output: PhantomData<fn() -> O>,
+}
+
+impl<I, O: Send, F: Fn(I) -> Option<O>> Task for MapTask<I, O, F> {
+    type Output = (usize, Option<O>);
+
+    fn run(&self) -> Option<Self::Output> {
+        let input = self.input.take().expect("a MapTask only runs once");
+        Some((self.index, (self.f)(input)))
+    }
+}
+
+// What WorkQueue::iter gives: blocks for each output, ends once every worker is gone.
+// A named struct rather than impl Iterator: the compiler can then see dropping it doesn't
+// touch the queue, so (as with mpsc::Iter) a loop over it can shut the queue down and break.
+pub struct OutputIter<'a, T> {
+    peeked: Option<T>,
+    rest: mpsc::Iter<'a, T>,
+}
+
+impl<T> Iterator for OutputIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.peeked.take().or_else(|| self.rest.next())
+    }
+}
+
+// State shared between a queue and all of its workers
+struct WorkerState {
+    cancelled: AtomicBool,  // see cancel()
+    closed: AtomicBool,     // shutdown has started: skip every task not already running
+    busy: AtomicUsize,      // number of workers currently running a task
+    work_enqueued: AtomicU64,   // sum of total_work() over every enqueued task
+    work_done: AtomicU64,       // ... and over every task that has finished running
+    tasks_sent: AtomicU64,      // tasks handed to the channel
+    tasks_settled: AtomicU64,   // ... that a worker has run (output sent) or skipped; see iter_n
+    settled_lock: Mutex<()>,    // held to check tasks_settled before waiting on all_settled
+    all_settled: Condvar,       // a task settled, or a worker exited; see join()
+    workers_running: AtomicUsize,   // worker threads that haven't returned yet
+    panicked: AtomicUsize,      // tasks whose run panicked; see panicked_tasks()
+    capacity: Option<usize>,    // most tasks waiting in the channel at once; None = unbounded
+    queued: Mutex<usize>,       // tasks in the channel no worker has taken yet (only kept when bounded)
+    slot_freed: Condvar,        // a worker took a task off a full channel
+}
+
+impl WorkerState {
+    // Block until the channel has room for one more task, and claim it
+    fn claim_slot(&self) {
+        if let Some(cap) = self.capacity {
+            let mut queued = self.queued.lock().unwrap();
+            while *queued >= cap {
+                queued = self.slot_freed.wait(queued).unwrap();
+            }
+            *queued += 1;
+        }
+    }
+
+    // A worker is done with a task, run or skipped. Takes settled_lock after counting it, so a
+    // join() that just saw the old count is already waiting and gets the notification.
+    fn settle_one(&self) {
+        self.tasks_settled.fetch_add(1, Ordering::SeqCst);
+        drop(self.settled_lock.lock().unwrap());
+        self.all_settled.notify_all();
+    }
+
+    // A worker thread is returning; same locking as settle_one
+    fn worker_exited(&self) {
+        self.workers_running.fetch_sub(1, Ordering::SeqCst);
+        drop(self.settled_lock.lock().unwrap());
+        self.all_settled.notify_all();
+    }
+
+    // A task left the channel (taken by a worker, or never sent)
+    fn release_slot(&self) {
+        if self.capacity.is_some() {
+            *self.queued.lock().unwrap() -= 1;
+            self.slot_freed.notify_one();
+        }
+    }
 }
 
 pub struct WorkQueue<TaskType: 'static + Task + Send> {
     send_tasks: Option<spmc::Sender<TaskType>>, // Option because it will be set to None to close the queue
     // spmc: distribute tasks to workers via 1 producer
 
-    recv_tasks: spmc::Receiver<TaskType>,
-    // drain thread pool when queue is being shut down
+    _recv_tasks: spmc::Receiver<TaskType>,
+    // never read: the workers drain the queue themselves (see shutdown); holding a receiver
+    // just keeps enqueue working when there are no workers left to receive
 
     //send_output: mpsc::Sender<TaskType::Output>, // not need in the struct: each worker will have its own clone.
     
@@ -22,40 +187,127 @@ pub struct WorkQueue<TaskType: 'static + Task + Send> {
     // mpsc: receive output from many workers
 
     workers: Vec<thread::JoinHandle<()>>,   // contain JoinHandles of each of the threads doing processing
+
+    state: Arc<WorkerState>,    // shared with every worker
+
+    next_seq: u64,      // sequence number the next successfully enqueued task gets
+
+    seen: HashSet<TaskType>,    // everything passed to enqueue_dedup so far
+
+    producer: Option<thread::JoinHandle<()>>,   // see spawn_producer
+
+    held: VecDeque<TaskType::Output>,   // outputs received by ok_iter/err_iter meant for the other one
+
+    peeked: RefCell<Option<TaskType::Output>>,  // taken off the channel by has_pending_output, not yet handed out
 }
 
 impl<TaskType: 'static + Task + Send> WorkQueue<TaskType> {
     pub fn new(n_workers: usize) -> WorkQueue<TaskType> {
         // TODO: create the channels; start the worker threads; record their JoinHandles
-        let (send_tasks, recv_tasks) = spmc::channel();
         let (send_output, recv_output) = mpsc::channel();
+        Self::with_output(n_workers, send_output, recv_output, None, None)
+    }
+
+    // Bounded mode, for backpressure: once `queue_cap` tasks are waiting for a worker, enqueue
+    // blocks until one is taken. Keeps memory flat when producing tasks is much cheaper than
+    // running them.
+    pub fn with_capacity(n_workers: usize, queue_cap: usize) -> WorkQueue<TaskType> {
+        assert!(queue_cap > 0, "a queue with capacity 0 could never take a task");
+        assert!(n_workers > 0, "a bounded queue with no workers would block enqueue once full");
+        let (send_output, recv_output) = mpsc::channel();
+        Self::with_output(n_workers, send_output, recv_output, None, Some(queue_cap))
+    }
+
+    // For deeply recursive tasks that would overflow the default thread stack
+    pub fn new_with_stack_size(n_workers: usize, stack_bytes: usize) -> WorkQueue<TaskType> {
+        let (send_output, recv_output) = mpsc::channel();
+        Self::with_output(n_workers, send_output, recv_output, Some(stack_bytes), None)
+    }
+
+    // Workers push their outputs into `sink` instead of the queue's own channel, so several
+    // queues can feed one consumer. The queue's recv/iter/try_recv never see anything.
+    pub fn new_with_output_sink(
+        n_workers: usize,
+        sink: mpsc::Sender<TaskType::Output>,
+    ) -> WorkQueue<TaskType> {
+        let (_, recv_output) = mpsc::channel();
+        Self::with_output(n_workers, sink, recv_output, None, None)
+    }
+
+    fn with_output(
+        n_workers: usize,
+        send_output: mpsc::Sender<TaskType::Output>,
+        recv_output: mpsc::Receiver<TaskType::Output>,
+        stack_size: Option<usize>,
+        capacity: Option<usize>,
+    ) -> WorkQueue<TaskType> {
+        let (send_tasks, recv_tasks) = spmc::channel();
+        let state = Arc::new(WorkerState {
+            cancelled: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            busy: AtomicUsize::new(0),
+            work_enqueued: AtomicU64::new(0),
+            work_done: AtomicU64::new(0),
+            tasks_sent: AtomicU64::new(0),
+            tasks_settled: AtomicU64::new(0),
+            settled_lock: Mutex::new(()),
+            all_settled: Condvar::new(),
+            workers_running: AtomicUsize::new(n_workers),
+            panicked: AtomicUsize::new(0),
+            capacity,
+            queued: Mutex::new(0),
+            slot_freed: Condvar::new(),
+        });
 
         // Create worker threads
         let mut workers = Vec::with_capacity(n_workers);
-        for _ in 0..n_workers {
+        for worker_id in 0..n_workers {
             let recv_tasks = recv_tasks.clone();
             let send_output = send_output.clone();
+            let state = state.clone();
 
-            let handle = thread::spawn(move || {
-                Self::run(recv_tasks, send_output);
-            });
+            let mut builder = thread::Builder::new();
+            if let Some(bytes) = stack_size {
+                builder = builder.stack_size(bytes);
+            }
+            let handle = builder
+                .spawn(move || {
+                    WORKER_ID.with(|id| id.set(Some(worker_id)));
+                    Self::run(recv_tasks, send_output, state.clone());
+                    state.worker_exited();
+                })
+                .expect("failed to spawn worker thread");
 
             workers.push(handle);
         }
 
         WorkQueue { 
             send_tasks: Some(send_tasks), 
-            recv_tasks,
+            _recv_tasks: recv_tasks,
             recv_output, 
-            workers
+            workers,
+            state,
+            next_seq: 0,
+            seen: HashSet::new(),
+            producer: None,
+            held: VecDeque::new(),
+            peeked: RefCell::new(None)
         }
     }
 
-    fn run(recv_tasks: spmc::Receiver<TaskType>, send_output: mpsc::Sender<TaskType::Output>) {
+    fn run(
+        recv_tasks: spmc::Receiver<TaskType>,
+        send_output: mpsc::Sender<TaskType::Output>,
+        state: Arc<WorkerState>,
+    ) {
         // TODO: the main logic for a worker thread
+        let should_continue = || !state.cancelled.load(Ordering::Relaxed);
         loop {
             // receive tasks
             let task_result = recv_tasks.recv();
+            if task_result.is_ok() {
+                state.release_slot();
+            }
             // NOTE: task_result will be Err() if the spmc::Sender has been destroyed and no more messages can be received here
             match task_result {
                 // channel is closed (sender dropped) => end the thread
@@ -63,16 +315,36 @@ impl<TaskType: 'static + Task + Send> WorkQueue<TaskType> {
                     return;
                 }
 
+                // cancelled or shutting down => don't start the task, just discard it
+                Ok(_) if !should_continue() || state.closed.load(Ordering::SeqCst) => {
+                    state.settle_one();
+                }
+
                 // run task
                 Ok(task) => {
+                    state.busy.fetch_add(1, Ordering::SeqCst);
+                    // a panicking task counts as finished with no output, and the worker
+                    // carries on: otherwise its thread dies and shutdown's join re-panics
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| task.run_cancellable(&should_continue)))
+                        .unwrap_or_else(|_| {
+                            state.panicked.fetch_add(1, Ordering::SeqCst);
+                            None
+                        });
+                    state.work_done.fetch_add(task.total_work(), Ordering::SeqCst);
+                    state.busy.fetch_sub(1, Ordering::SeqCst);
+
                     // check task result
-                    if let Some(output) = task.run() {
+                    if let Some(output) = result {
                         
                         // case: cannot send
                         if send_output.send(output).is_err() {
+                            // still settled, or join would wait for this task forever
+                            state.settle_one();
                             return;
                         }
                     }
+                    // only after the send, so iter_n never sees a task settled before its output
+                    state.settle_one();
                     // if the outermost if is false => task result is None => do nothing and continue
 
                     // PATTERN MATCH APPROACH
@@ -93,56 +365,346 @@ impl<TaskType: 'static + Task + Send> WorkQueue<TaskType> {
 
     pub fn enqueue(&mut self, t: TaskType) -> Result<(), spmc::SendError<TaskType>> {
         // TODO: send this task to a worker
+        self.enqueue_sequenced(t).map(|_| ())
+    }
+
+    // Same as enqueue, but gives back the sequence number (0, 1, 2, ...) assigned to the task,
+    // so callers can keep their own seq => metadata maps
+    pub fn enqueue_sequenced(&mut self, t: TaskType) -> Result<u64, spmc::SendError<TaskType>> {
+        let work = t.total_work();
         match self.send_tasks.as_mut() {
-            Some(sender) => sender.send(t),     // send modifies sender => sender must be mut => use as.mut()
+            Some(sender) => {
+                self.state.claim_slot();
+                // counted before sending: a worker may settle the task before send returns
+                self.state.tasks_sent.fetch_add(1, Ordering::SeqCst);
+                // send modifies sender => sender must be mut => use as.mut()
+                if let Err(e) = sender.send(t) {
+                    self.state.tasks_sent.fetch_sub(1, Ordering::SeqCst);
+                    self.state.release_slot();
+                    return Err(e);
+                }
+            }
             None => panic!()
         }
+        self.state.work_enqueued.fetch_add(work, Ordering::SeqCst);
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        Ok(seq)
     }
 
-    // Helper methods that let you receive results in various ways
-    pub fn iter(&mut self) -> mpsc::Iter<TaskType::Output> {
-        self.recv_output.iter()
+    // Enqueue every task in order, giving how many went in. Stops at the first one that can't be sent.
+    pub fn enqueue_all<I: IntoIterator<Item = TaskType>>(
+        &mut self,
+        tasks: I,
+    ) -> Result<usize, spmc::SendError<TaskType>> {
+        let mut n = 0;
+        for t in tasks {
+            self.enqueue(t)?;
+            n += 1;
+        }
+        Ok(n)
+    }
+
+    // Hand task production over to a thread that enqueues whatever `gen` gives until it returns None.
+    // The producer takes the queue's sender with it, so enqueue can't be used afterwards; the queue
+    // closes when the producer is done, which lets iter() end once every task has been run.
+    pub fn spawn_producer(&mut self, mut gen: impl FnMut() -> Option<TaskType> + Send + 'static) {
+        let mut sender = self.send_tasks.take().expect("queue is already closed to new tasks");
+        let state = self.state.clone();
+        self.producer = Some(thread::spawn(move || {
+            // stop once shutdown or cancel means nothing produced from now on would be run:
+            // an endless generator would otherwise keep shutdown waiting forever
+            while !state.closed.load(Ordering::SeqCst) && !state.cancelled.load(Ordering::Relaxed) {
+                let t = match gen() {
+                    Some(t) => t,
+                    None => return,
+                };
+                let work = t.total_work();
+                state.claim_slot();
+                state.tasks_sent.fetch_add(1, Ordering::SeqCst);
+                if sender.send(t).is_err() {
+                    state.tasks_sent.fetch_sub(1, Ordering::SeqCst);
+                    return;
+                }
+                state.work_enqueued.fetch_add(work, Ordering::SeqCst);
+            }
+        }));
+    }
+
+    // Helper methods that let you receive results in various ways.
+    // All of them hand out an output peeked by has_pending_output before going to the channel.
+    pub fn iter(&mut self) -> OutputIter<'_, TaskType::Output> {
+        OutputIter {
+            peeked: self.peeked.get_mut().take(),
+            rest: self.recv_output.iter(),
+        }
+    }
+    // Like iter, but ends after `n` outputs, or sooner once every task enqueued so far has been
+    // run and its output received, so it doesn't hang when some tasks produce no output
+    pub fn iter_n(&mut self, n: usize) -> impl Iterator<Item = TaskType::Output> + '_ {
+        let mut remaining = n;
+        std::iter::from_fn(move || {
+            if remaining == 0 {
+                return None;
+            }
+            let output = self.next_output_unless_settled()?;
+            remaining -= 1;
+            Some(output)
+        })
     }
     pub fn recv(&mut self) -> TaskType::Output {
-        self.recv_output
-            .recv()
-            .expect("I have been shutdown incorrectly")
+        self.next_output().expect("I have been shutdown incorrectly")
     }
     pub fn try_recv(&mut self) -> Result<TaskType::Output, mpsc::TryRecvError> {
-        self.recv_output.try_recv()
+        match self.peeked.get_mut().take() {
+            Some(output) => Ok(output),
+            None => self.recv_output.try_recv(),
+        }
     }
     pub fn recv_timeout(
         &self,
-        timeout: std::time::Duration,
+        timeout: Duration,
     ) -> Result<TaskType::Output, mpsc::RecvTimeoutError> {
-        self.recv_output.recv_timeout(timeout)
+        match self.peeked.borrow_mut().take() {
+            Some(output) => Ok(output),
+            None => self.recv_output.recv_timeout(timeout),
+        }
+    }
+
+    // Blocking receive; None once every worker is gone and the channel is empty
+    fn next_output(&mut self) -> Option<TaskType::Output> {
+        self.peeked.get_mut().take().or_else(|| self.recv_output.recv().ok())
+    }
+
+    // Blocking receive that gives up with None once there's nothing left to wait for: every task
+    // sent has settled and the channel is empty (or every worker is gone)
+    fn next_output_unless_settled(&mut self) -> Option<TaskType::Output> {
+        const POLL: Duration = Duration::from_millis(10);
+        loop {
+            // read before looking at the channel: once every task has settled, all their
+            // outputs are already in it
+            let settled = self.state.tasks_settled.load(Ordering::SeqCst)
+                >= self.state.tasks_sent.load(Ordering::SeqCst);
+            match self.try_recv() {
+                Ok(output) => return Some(output),
+                Err(mpsc::TryRecvError::Disconnected) => return None,
+                Err(mpsc::TryRecvError::Empty) if settled => return None,
+                Err(mpsc::TryRecvError::Empty) => match self.recv_output.recv_timeout(POLL) {
+                    Ok(output) => return Some(output),
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return None,
+                },
+            }
+        }
+    }
+
+    // Is an output ready to receive right now? Doesn't block, and the output isn't lost:
+    // the next recv/try_recv/iter/... gets it.
+    pub fn has_pending_output(&self) -> bool {
+        let mut peeked = self.peeked.borrow_mut();
+        if peeked.is_none() {
+            *peeked = self.recv_output.try_recv().ok();
+        }
+        peeked.is_some()
+    }
+
+    // Wait until every task enqueued so far has been run (or skipped), without closing the queue:
+    // their outputs are all receivable once this returns, and the pool is ready for the next batch.
+    // Tasks a producer enqueues while this waits may or may not be waited for. Once no worker is
+    // running (a pool of 0, or one that's been shut down) it returns, whatever is left behind.
+    pub fn join(&self) {
+        // with every worker gone, nothing is left to settle whatever is still outstanding
+        let mut guard = self.state.settled_lock.lock().unwrap();
+        while self.state.tasks_settled.load(Ordering::SeqCst) < self.state.tasks_sent.load(Ordering::SeqCst)
+            && self.state.workers_running.load(Ordering::SeqCst) > 0
+        {
+            guard = self.state.all_settled.wait(guard).unwrap();
+        }
+    }
+
+    // Worker threads that haven't been joined yet: all of them until shutdown, which joins them, or
+    // shutdown_timeout, which joins the ones that finished in time and detaches the rest (0 after either).
+    pub fn active_workers(&self) -> usize {
+        self.workers.len()
+    }
+
+    // Receive exactly `n` outputs, e.g. one per task when every task produces one
+    pub fn collect(&mut self, n: usize) -> Vec<TaskType::Output> {
+        (0..n).map(|_| self.recv()).collect()
+    }
+
+    // Collect outputs until none arrives for `idle`: adapts to however long a burst lasts.
+    // Waits up to `idle` for the first one, so gives an empty Vec if nothing is coming.
+    pub fn recv_until_idle(&mut self, idle: Duration) -> Vec<TaskType::Output> {
+        let mut batch = Vec::new();
+        while let Ok(output) = self.recv_timeout(idle) {
+            batch.push(output);
+        }
+        batch
+    }
+
+    // Ask the workers to stop early: tasks polling should_continue give up, queued tasks are skipped.
+    // The queue is still usable afterwards, but every task enqueued later is skipped too.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+
+    // How many tasks have panicked so far. Their panic is caught and they produce no output,
+    // so a caller waiting for one output per task should check this.
+    pub fn panicked_tasks(&self) -> usize {
+        self.state.panicked.load(Ordering::SeqCst)
+    }
+
+    // Fraction of workers busy running a task right now: 0.0 = all idle, 1.0 = all busy.
+    // 0.0 once the queue has been shut down.
+    pub fn current_load(&self) -> f64 {
+        if self.workers.is_empty() {
+            return 0.0;
+        }
+        self.state.busy.load(Ordering::SeqCst) as f64 / self.workers.len() as f64
     }
 
+    // Finished work over enqueued work, weighted by Task::total_work, for an overall progress bar.
+    // 0.0 before anything is enqueued. Tasks skipped by cancel/shutdown never count as finished.
+    pub fn progress_fraction(&self) -> f64 {
+        let total = self.state.work_enqueued.load(Ordering::SeqCst);
+        if total == 0 {
+            return 0.0;
+        }
+        self.state.work_done.load(Ordering::SeqCst) as f64 / total as f64
+    }
+
+    // One-shot batch pattern: close the queue to new tasks and yield every output as the workers
+    // get through what's queued, ending once they are all done. Dropping the iterator early
+    // shuts the queue down, abandoning whatever hasn't started.
+    pub fn into_results(mut self) -> impl Iterator<Item = TaskType::Output> {
+        self.send_tasks = None;
+        std::iter::from_fn(move || self.next_output())
+    }
+
+    // Race-to-first: block for the first output, then cancel the tasks still running, abandon
+    // everything still queued and shut down.
+    // Gives None if every task finished without producing an output.
+    pub fn await_first(&mut self) -> Option<TaskType::Output> {
+        // no more tasks are coming, so once the queue empties the workers exit and drop their
+        // output senders => recv() gives Err instead of blocking forever
+        self.send_tasks = None;
+        let first = self.next_output();
+        self.cancel();
+        self.shutdown();
+        first
+    }
+
+    // Stop accepting tasks, let the tasks already running finish (their outputs stay receivable),
+    // and discard every task that hasn't started, then wait for the workers to exit.
     pub fn shutdown(&mut self) {
         // TODO: destroy the spmc::Sender so everybody knows no more tasks are incoming;
         // drain any pending tasks in the queue; wait for each worker thread to finish.
         // HINT: Vec.drain(..)
+        // No draining here: the workers take what's left off the queue themselves and skip it.
+        // Draining as well would race them for it, so whether a queued task ran came down to timing.
+        self.state.closed.store(true, Ordering::SeqCst);
         self.send_tasks = None;     // destroy spmc::Sender => no more tasks can be sent
-        // drain remaining task from the queue
-        loop {
-            match self.recv_tasks.recv() {    
-                Ok(_) => (),
-                Err(_) => break     // end of queue 
-            }
+        // a producer owns the sender instead: it has to finish before the queue closes
+        if let Some(producer) = self.producer.take() {
+            producer.join().unwrap();
         }
 
         for handle in self.workers.drain(..) {
             handle.join().unwrap();
         }
     }
+
+    // shutdown, but waits at most `bound` for the producer and workers to exit. Queued tasks are
+    // skipped just the same. Whatever exited in time is joined; a worker still busy with a task
+    // when time is up (or a producer still generating one) is detached: it finishes on its own, its
+    // output still arrives, and neither a later shutdown nor the drop waits for it.
+    pub fn shutdown_timeout(&mut self, bound: Duration) {
+        self.state.closed.store(true, Ordering::SeqCst);
+        self.send_tasks = None;
+        let deadline = Instant::now() + bound;
+        // the producer stops at its next task, but that may take a while to generate
+        while self.producer.as_ref().is_some_and(|h| !h.is_finished()) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        if let Some(producer) = self.producer.take() {
+            if producer.is_finished() {
+                producer.join().unwrap();
+            }
+        }
+        while self.workers.iter().any(|h| !h.is_finished()) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // dropping a JoinHandle detaches its thread
+        for handle in self.workers.drain(..) {
+            if handle.is_finished() {
+                handle.join().unwrap();
+            }
+        }
+    }
+}
+
+impl<TaskType: 'static + Task + Send + Hash + Eq + Clone> WorkQueue<TaskType> {
+    // Enqueue the task unless an equal one has already gone through enqueue_dedup.
+    // Ok(true) => it was new and got queued; Ok(false) => duplicate, skipped.
+    pub fn enqueue_dedup(&mut self, t: TaskType) -> Result<bool, spmc::SendError<TaskType>> {
+        if self.seen.contains(&t) {
+            return Ok(false);
+        }
+        self.enqueue(t.clone())?;
+        self.seen.insert(t);
+        Ok(true)
+    }
+}
+
+// A queue of fallible tasks, whose successes and failures can be consumed separately.
+// Like iter(), both iterators end once the queue is shut down and every output is received.
+impl<T: Send, E: Send, TaskType: 'static + Task<Output = Result<T, E>> + Send> WorkQueue<TaskType> {
+    pub fn ok_iter(&mut self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.next_matching(true).and_then(Result::ok))
+    }
+
+    pub fn err_iter(&mut self) -> impl Iterator<Item = E> + '_ {
+        std::iter::from_fn(move || self.next_matching(false).and_then(Result::err))
+    }
+
+    // Next output that is_ok() == want_ok: held ones first, holding on to the others as they arrive
+    fn next_matching(&mut self, want_ok: bool) -> Option<Result<T, E>> {
+        if let Some(i) = self.held.iter().position(|r| r.is_ok() == want_ok) {
+            return self.held.remove(i);
+        }
+        while let Some(r) = self.next_output() {
+            if r.is_ok() == want_ok {
+                return Some(r);
+            }
+            self.held.push_back(r);
+        }
+        None
+    }
+}
+
+// A queue whose outputs come back as (worker index, output), for watching how work is spread
+impl<T: 'static + Task + Send> WorkQueue<Attributed<T>> {
+    pub fn new_attributed(n_workers: usize) -> WorkQueue<Attributed<T>> {
+        Self::new(n_workers)
+    }
+
+    pub fn enqueue_attributed(&mut self, t: T) -> Result<(), spmc::SendError<Attributed<T>>> {
+        self.enqueue(Attributed(t))
+    }
 }
 
 impl<TaskType: 'static + Task + Send> Drop for WorkQueue<TaskType> {
     fn drop(&mut self) {
         // "Finalisation in destructors" pattern: https://rust-unofficial.github.io/patterns/idioms/dtor-finally.html
-        match self.send_tasks {
-            None => {} // already shut down
-            Some(_) => self.shutdown(),
+        // closed to new tasks isn't enough (into_results): the workers have to be joined too
+        if self.send_tasks.is_some() || self.producer.is_some() || !self.workers.is_empty() {
+            self.shutdown();
         }
     }
 }
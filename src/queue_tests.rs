@@ -1,6 +1,7 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod queue_tests {
-    use crate::queue::{Task, WorkQueue};
+    use crate::queue::{current_worker, par_map, Attributed, Task, WorkQueue};
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::time::{Duration, Instant};
     use std::{sync, thread, time};
@@ -21,6 +22,19 @@ mod queue_tests {
         }
     }
 
+    #[derive(Debug)]
+    struct DelayTask {
+        delay: time::Duration,
+        result: i64,
+    }
+    impl Task for DelayTask {
+        type Output = i64;
+        fn run(&self) -> Option<i64> {
+            thread::sleep(self.delay);
+            Some(self.result)
+        }
+    }
+
     #[test]
     // Test that the work queue can do jobs and get correct results back.
     fn basics() {
@@ -154,4 +168,773 @@ mod queue_tests {
             "work continued after .shutdown(): threads were leaked because they weren't joined"
         );
     }
+
+    #[test]
+    // Test that two queues can push their outputs into one caller-supplied channel.
+    fn shared_output_sink() {
+        let n_tasks: usize = 6;
+        let n_run = sync::Arc::<AtomicUsize>::new(0.into());
+        let (sink, results) = std::sync::mpsc::channel();
+
+        let mut q1 = WorkQueue::<TestTask>::new_with_output_sink(2, sink.clone());
+        let mut q2 = WorkQueue::<TestTask>::new_with_output_sink(2, sink);
+
+        for _ in 0..n_tasks {
+            q1.enqueue(TestTask {
+                counter: n_run.clone(),
+            })
+            .unwrap();
+            q2.enqueue(TestTask {
+                counter: n_run.clone(),
+            })
+            .unwrap();
+        }
+
+        // Everything arrives on the shared channel; the queues' own receivers stay empty.
+        for _ in 0..2 * n_tasks {
+            let r = results.recv().unwrap();
+            assert_eq!(r, CORRECT_RESULT);
+        }
+        assert!(q1.try_recv().is_err());
+        assert!(q2.try_recv().is_err());
+
+        // Once both queues are shut down, every sender is gone and the sink disconnects.
+        q1.shutdown();
+        q2.shutdown();
+        assert!(results.recv().is_err());
+        assert_eq!((*n_run).load(Ordering::SeqCst), 2 * n_tasks);
+    }
+
+    #[test]
+    // Test that await_first returns the first output and drops the rest of the queue.
+    fn await_first() {
+        let n_threads: usize = 2;
+        let n_slow: usize = 20;
+
+        let mut q = WorkQueue::<DelayTask>::new(n_threads);
+        q.enqueue(DelayTask {
+            delay: Duration::from_millis(10),
+            result: CORRECT_RESULT,
+        })
+        .unwrap();
+        for _ in 0..n_slow {
+            q.enqueue(DelayTask {
+                delay: DELAY,
+                result: 0,
+            })
+            .unwrap();
+        }
+
+        let start = Instant::now();
+        assert_eq!(q.await_first(), Some(CORRECT_RESULT));
+
+        // Only the slow task already in flight should be waited for, not the whole backlog.
+        let time_taken = Instant::now().duration_since(start);
+        assert!(time_taken < 3 * DELAY, "await_first waited for queued tasks");
+    }
+
+    #[test]
+    // Test that await_first gives None when no task produces an output.
+    fn await_first_none() {
+        #[derive(Debug)]
+        struct NothingTask;
+        impl Task for NothingTask {
+            type Output = i64;
+            fn run(&self) -> Option<i64> {
+                None
+            }
+        }
+
+        let mut q = WorkQueue::<NothingTask>::new(2);
+        for _ in 0..5 {
+            q.enqueue(NothingTask).unwrap();
+        }
+        assert_eq!(q.await_first(), None);
+    }
+
+    #[test]
+    // Test that shutdown_timeout gets through a big backlog quickly and, with time for the running tasks, joins every worker.
+    fn shutdown_timeout() {
+        let n_threads: usize = 4;
+        let n_tasks: usize = 4000;
+        let n_run = sync::Arc::<AtomicUsize>::new(0.into());
+
+        let mut q = WorkQueue::<TestTask>::new(n_threads);
+        for _ in 0..n_tasks {
+            q.enqueue(TestTask {
+                counter: n_run.clone(),
+            })
+            .unwrap();
+        }
+
+        let start = Instant::now();
+        q.shutdown_timeout(5 * DELAY);
+        let time_taken = Instant::now().duration_since(start);
+        assert!(time_taken < 8 * DELAY, "shutdown_timeout took too long");
+        assert_eq!(q.active_workers(), 0);
+
+        // Every worker finished its task within the bound and was joined, so none is still running.
+        let before: usize = (*n_run).load(Ordering::SeqCst);
+        thread::sleep(2 * DELAY);
+        assert_eq!(before, (*n_run).load(Ordering::SeqCst));
+        assert!(before < n_tasks);
+    }
+
+    #[derive(Debug)]
+    struct SpinTask {
+        iterations: sync::Arc<AtomicUsize>,
+    }
+    impl Task for SpinTask {
+        type Output = i64;
+        fn run(&self) -> Option<i64> {
+            self.run_cancellable(&|| true)
+        }
+        fn run_cancellable(&self, should_continue: &dyn Fn() -> bool) -> Option<i64> {
+            // effectively endless unless cancelled
+            for _ in 0..u64::MAX {
+                if !should_continue() {
+                    return None;
+                }
+                self.iterations.fetch_add(1, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(1));
+            }
+            Some(CORRECT_RESULT)
+        }
+    }
+
+    #[test]
+    // Test that a long task polling should_continue stops soon after the queue is cancelled.
+    fn cancel_long_task() {
+
+        let iterations = sync::Arc::<AtomicUsize>::new(0.into());
+        let mut q = WorkQueue::<SpinTask>::new(1);
+        q.enqueue(SpinTask {
+            iterations: iterations.clone(),
+        })
+        .unwrap();
+
+        thread::sleep(DELAY);
+        q.cancel();
+        let at_cancel = (*iterations).load(Ordering::SeqCst);
+        assert!(at_cancel > 0);
+
+        // The worker is joined, so the task must have returned; it got at most one more iteration.
+        q.shutdown();
+        let after = (*iterations).load(Ordering::SeqCst);
+        assert!(after - at_cancel <= 1, "task kept running after cancel");
+        assert!(q.try_recv().is_err());
+    }
+
+    #[test]
+    // Test that tasks still queued when the queue is cancelled are never started.
+    fn cancel_skips_queued() {
+        let n_threads: usize = 2;
+        let n_tasks: usize = 20;
+        let n_run = sync::Arc::<AtomicUsize>::new(0.into());
+
+        let mut q = WorkQueue::<TestTask>::new(n_threads);
+        for _ in 0..n_tasks {
+            q.enqueue(TestTask {
+                counter: n_run.clone(),
+            })
+            .unwrap();
+        }
+        thread::sleep(DELAY / 2);
+        q.cancel();
+        assert!(q.is_cancelled());
+
+        // only the n_threads tasks already running when cancelled get to finish
+        thread::sleep(3 * DELAY);
+        assert_eq!((*n_run).load(Ordering::SeqCst), n_threads);
+        q.shutdown();
+    }
+
+    #[test]
+    // Test that attributed outputs carry the index of the worker that ran the task.
+    fn attributed() {
+        let n_threads: usize = 3;
+        let n_tasks: usize = 3 * n_threads;
+        let n_run = sync::Arc::<AtomicUsize>::new(0.into());
+
+        let mut q = WorkQueue::<Attributed<TestTask>>::new_attributed(n_threads);
+        for _ in 0..n_tasks {
+            q.enqueue_attributed(TestTask {
+                counter: n_run.clone(),
+            })
+            .unwrap();
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..n_tasks {
+            let (worker, r) = q.recv();
+            assert_eq!(r, CORRECT_RESULT);
+            assert!(worker < n_threads);
+            seen.insert(worker);
+        }
+        // tasks take long enough that every worker gets some
+        assert_eq!(seen.len(), n_threads);
+        assert_eq!(current_worker(), None);
+    }
+
+    #[test]
+    // Test that workers get the requested stack size: this recursion overflows the default 2MiB.
+    fn stack_size() {
+        #[derive(Debug)]
+        struct DeepTask {
+            depth: u64,
+        }
+        fn recurse(depth: u64) -> u64 {
+            let frame = std::hint::black_box([depth as u8; 512]);
+            if depth == 0 {
+                return 0;
+            }
+            frame[0] as u64 % 2 + recurse(depth - 1)
+        }
+        impl Task for DeepTask {
+            type Output = u64;
+            fn run(&self) -> Option<u64> {
+                Some(recurse(self.depth))
+            }
+        }
+
+        let mut q = WorkQueue::<DeepTask>::new_with_stack_size(2, 64 * 1024 * 1024);
+        for _ in 0..4 {
+            q.enqueue(DeepTask { depth: 20_000 }).unwrap();
+        }
+        for _ in 0..4 {
+            assert_eq!(q.recv(), 10_000);
+        }
+    }
+
+    #[test]
+    // Test that enqueued tasks are numbered in order, counting plain enqueue calls too.
+    fn enqueue_sequenced() {
+        let n_run = sync::Arc::<AtomicUsize>::new(0.into());
+        let mut q = WorkQueue::<TestTask>::new(2);
+
+        for expected in 0..3 {
+            let seq = q
+                .enqueue_sequenced(TestTask {
+                    counter: n_run.clone(),
+                })
+                .unwrap();
+            assert_eq!(seq, expected);
+        }
+        q.enqueue(TestTask {
+            counter: n_run.clone(),
+        })
+        .unwrap();
+        let seq = q
+            .enqueue_sequenced(TestTask {
+                counter: n_run.clone(),
+            })
+            .unwrap();
+        assert_eq!(seq, 4);
+    }
+
+    #[test]
+    // Test that enqueue_dedup skips tasks equal to ones it has already queued.
+    fn enqueue_dedup() {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        struct RangeTask {
+            start: i64,
+            end: i64,
+        }
+        impl Task for RangeTask {
+            type Output = i64;
+            fn run(&self) -> Option<i64> {
+                Some(self.start)
+            }
+        }
+
+        let mut q = WorkQueue::<RangeTask>::new(2);
+        assert!(q.enqueue_dedup(RangeTask { start: 0, end: 10 }).unwrap());
+        assert!(!q.enqueue_dedup(RangeTask { start: 0, end: 10 }).unwrap());
+        assert!(q.enqueue_dedup(RangeTask { start: 10, end: 20 }).unwrap());
+
+        let mut results = vec![q.recv(), q.recv()];
+        results.sort();
+        assert_eq!(results, vec![0, 10]);
+
+        // the duplicate never ran, so nothing else shows up
+        thread::sleep(DELAY);
+        assert!(q.try_recv().is_err());
+    }
+
+    #[test]
+    // Test that recv_until_idle collects a whole burst and stops once results dry up.
+    fn recv_until_idle() {
+        let n_threads: usize = 4;
+        let n_tasks: usize = 20;
+
+        let mut q = WorkQueue::<DelayTask>::new(n_threads);
+        for _ in 0..n_tasks {
+            q.enqueue(DelayTask {
+                delay: Duration::from_millis(10),
+                result: CORRECT_RESULT,
+            })
+            .unwrap();
+        }
+
+        let burst = q.recv_until_idle(DELAY);
+        assert_eq!(burst.len(), n_tasks);
+        assert!(burst.iter().all(|&r| r == CORRECT_RESULT));
+
+        // quiet now: waits one idle gap and comes back empty
+        let start = Instant::now();
+        assert!(q.recv_until_idle(DELAY).is_empty());
+        assert!(Instant::now().duration_since(start) >= DELAY);
+    }
+
+    #[test]
+    // Test that current_load is ~1 while every worker is busy and 0 once they're idle.
+    fn current_load() {
+        let n_threads: usize = 2;
+        let n_tasks: usize = 2 * n_threads;
+        let n_run = sync::Arc::<AtomicUsize>::new(0.into());
+
+        let mut q = WorkQueue::<TestTask>::new(n_threads);
+        assert_eq!(q.current_load(), 0.0);
+        for _ in 0..n_tasks {
+            q.enqueue(TestTask {
+                counter: n_run.clone(),
+            })
+            .unwrap();
+        }
+
+        thread::sleep(DELAY / 4);
+        assert_eq!(q.current_load(), 1.0);
+
+        for _ in 0..n_tasks {
+            q.recv();
+        }
+        thread::sleep(DELAY / 4);
+        assert_eq!(q.current_load(), 0.0);
+
+        q.shutdown();
+        assert_eq!(q.current_load(), 0.0);
+    }
+
+    #[test]
+    // Test that await_first stops tasks that are still running once it has its answer.
+    fn await_first_cancels_running() {
+        #[derive(Debug)]
+        enum RaceTask {
+            Spin(SpinTask), // never finishes unless cancelled
+            Fast,
+        }
+        impl Task for RaceTask {
+            type Output = i64;
+            fn run(&self) -> Option<i64> {
+                self.run_cancellable(&|| true)
+            }
+            fn run_cancellable(&self, should_continue: &dyn Fn() -> bool) -> Option<i64> {
+                match self {
+                    RaceTask::Spin(t) => t.run_cancellable(should_continue),
+                    RaceTask::Fast => {
+                        thread::sleep(DELAY / 4);
+                        Some(CORRECT_RESULT)
+                    }
+                }
+            }
+        }
+
+        let n_threads: usize = 4;
+        let iterations = sync::Arc::<AtomicUsize>::new(0.into());
+
+        let mut q = WorkQueue::<RaceTask>::new(n_threads);
+        for _ in 0..n_threads - 1 {
+            q.enqueue(RaceTask::Spin(SpinTask {
+                iterations: iterations.clone(),
+            }))
+            .unwrap();
+        }
+        q.enqueue(RaceTask::Fast).unwrap();
+
+        // Without cancellation the spinning workers would never finish and shutdown would hang.
+        let start = Instant::now();
+        assert_eq!(q.await_first(), Some(CORRECT_RESULT));
+        assert!(Instant::now().duration_since(start) < DELAY);
+        assert!((*iterations).load(Ordering::SeqCst) > 0);
+    }
+
+    #[test]
+    // Test that progress_fraction weighs tasks by total_work and climbs to 1 as they finish.
+    fn progress_fraction() {
+        #[derive(Debug)]
+        struct SizedTask {
+            size: u64,
+        }
+        impl Task for SizedTask {
+            type Output = u64;
+            fn run(&self) -> Option<u64> {
+                thread::sleep(DELAY);
+                Some(self.size)
+            }
+            fn total_work(&self) -> u64 {
+                self.size
+            }
+        }
+
+        let mut q = WorkQueue::<SizedTask>::new(1);
+        assert_eq!(q.progress_fraction(), 0.0);
+        q.enqueue(SizedTask { size: 3 }).unwrap();
+        q.enqueue(SizedTask { size: 1 }).unwrap();
+        assert_eq!(q.progress_fraction(), 0.0);
+
+        // one worker => the big task finishes first: 3 of 4 units
+        assert_eq!(q.recv(), 3);
+        thread::sleep(DELAY / 4);
+        assert_eq!(q.progress_fraction(), 0.75);
+
+        assert_eq!(q.recv(), 1);
+        thread::sleep(DELAY / 4);
+        assert_eq!(q.progress_fraction(), 1.0);
+    }
+
+    #[test]
+    // Test that a producer thread can feed the queue while the main thread collects outputs.
+    fn spawn_producer() {
+        let mut q = WorkQueue::<DelayTask>::new(3);
+        let mut next = 0;
+        q.spawn_producer(move || {
+            next += 1;
+            if next > 10 {
+                return None;
+            }
+            Some(DelayTask {
+                delay: Duration::from_millis(10),
+                result: next,
+            })
+        });
+
+        // the queue closes when the producer is done, so iter() ends after the 10th output
+        let mut results: Vec<i64> = q.iter().collect();
+        results.sort();
+        assert_eq!(results, (1..=10).collect::<Vec<i64>>());
+        assert_eq!(q.progress_fraction(), 1.0);
+    }
+
+    #[test]
+    // Test that an endless producer stops once the queue is shut down or cancelled.
+    fn spawn_producer_endless() {
+        let endless = || {
+            let mut next = 0;
+            move || {
+                next += 1;
+                Some(DelayTask { delay: Duration::from_millis(1), result: next })
+            }
+        };
+
+        let mut q = WorkQueue::<DelayTask>::new(2);
+        q.spawn_producer(endless());
+        thread::sleep(DELAY / 4);
+        let start = Instant::now();
+        q.shutdown();
+        assert!(Instant::now().duration_since(start) < DELAY);
+
+        let mut q = WorkQueue::<DelayTask>::with_capacity(2, 4);
+        q.spawn_producer(endless());
+        thread::sleep(DELAY / 4);
+        let start = Instant::now();
+        q.shutdown_timeout(DELAY);
+        assert!(Instant::now().duration_since(start) < DELAY * 2);
+
+        // cancelling stops it too, and then the queue closes
+        let mut q = WorkQueue::<DelayTask>::new(2);
+        q.spawn_producer(endless());
+        thread::sleep(DELAY / 4);
+        q.cancel();
+        assert!(q.iter().count() > 0);
+    }
+
+    #[test]
+    // Test that enqueue_all reports how many tasks went in and collect gets one output per task.
+    fn enqueue_all_collect() {
+        let mut q = WorkQueue::<DelayTask>::new(3);
+        let n = q
+            .enqueue_all((0..7).map(|i| DelayTask {
+                delay: Duration::from_millis(10),
+                result: i,
+            }))
+            .unwrap();
+        assert_eq!(n, 7);
+
+        let mut results = q.collect(n);
+        results.sort();
+        assert_eq!(results, (0..7).collect::<Vec<i64>>());
+        assert_eq!(q.enqueue_all(Vec::new()).unwrap(), 0);
+        assert!(q.collect(0).is_empty());
+    }
+
+    #[test]
+    // Test that ok_iter and err_iter each get only their side of Result outputs, whichever goes first.
+    fn ok_err_iter() {
+        #[derive(Debug)]
+        struct CheckedTask(i64);
+        impl Task for CheckedTask {
+            type Output = Result<i64, String>;
+            fn run(&self) -> Option<Result<i64, String>> {
+                if self.0 % 3 == 0 {
+                    Some(Err(format!("{} is a multiple of 3", self.0)))
+                } else {
+                    Some(Ok(self.0))
+                }
+            }
+        }
+
+        let mut q = WorkQueue::<CheckedTask>::new(2);
+        q.enqueue_all((1..=9).map(CheckedTask)).unwrap();
+        // let the workers run all 9, then close the queue so the iterators end
+        q.join();
+        q.shutdown();
+
+        let mut oks: Vec<i64> = q.ok_iter().collect();
+        oks.sort();
+        assert_eq!(oks, vec![1, 2, 4, 5, 7, 8]);
+        let mut errs: Vec<String> = q.err_iter().collect();
+        errs.sort();
+        assert_eq!(errs, vec!["3 is a multiple of 3", "6 is a multiple of 3", "9 is a multiple of 3"]);
+    }
+
+    #[test]
+    // Test that a panicking task doesn't take its worker down and gets counted.
+    fn panicked_tasks() {
+        #[derive(Debug)]
+        struct FragileTask(i64);
+        impl Task for FragileTask {
+            type Output = i64;
+            fn run(&self) -> Option<i64> {
+                if self.0 % 2 == 0 {
+                    panic!("task {} fell over", self.0);
+                }
+                Some(self.0)
+            }
+        }
+
+        // one worker => it has to survive the panics to get to the odd tasks
+        let mut q = WorkQueue::<FragileTask>::new(1);
+        q.enqueue_all((0..6).map(FragileTask)).unwrap();
+        assert_eq!(q.collect(3), vec![1, 3, 5]);
+        q.shutdown();
+        assert_eq!(q.panicked_tasks(), 3);
+        assert_eq!(q.progress_fraction(), 1.0);
+    }
+
+    #[test]
+    // Test that a bounded queue makes enqueue wait for the worker instead of piling tasks up.
+    fn with_capacity() {
+        let mut q = WorkQueue::<DelayTask>::with_capacity(1, 2);
+        let start = Instant::now();
+        // the worker takes task 0 straight away and tasks 1, 2 fill the queue:
+        // task 3 has to wait for task 0 to finish and task 4 for task 1
+        for i in 0..5 {
+            q.enqueue(DelayTask { delay: DELAY, result: i }).unwrap();
+        }
+        assert!(Instant::now().duration_since(start) >= DELAY);
+        assert_eq!(q.collect(5), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "no workers")]
+    // Test that a bounded queue refuses to start without workers, rather than block enqueue for good.
+    fn with_capacity_no_workers() {
+        WorkQueue::<DelayTask>::with_capacity(0, 2);
+    }
+
+    #[test]
+    // Test that into_results gives every output and then ends.
+    fn into_results() {
+        let mut q = WorkQueue::<DelayTask>::new(3);
+        q.enqueue_all((0..10).map(|i| DelayTask {
+            delay: Duration::from_millis(10),
+            result: i,
+        }))
+        .unwrap();
+
+        let mut results: Vec<i64> = q.into_results().collect();
+        results.sort();
+        assert_eq!(results, (0..10).collect::<Vec<i64>>());
+    }
+
+    #[test]
+    // Test that has_pending_output peeks without losing the output and active_workers counts un-joined workers.
+    fn has_pending_output_active_workers() {
+        let mut q = WorkQueue::<DelayTask>::new(2);
+        assert_eq!(q.active_workers(), 2);
+        assert!(!q.has_pending_output());
+
+        q.enqueue(DelayTask { delay: DELAY, result: 1 }).unwrap();
+        q.enqueue(DelayTask { delay: DELAY * 2, result: 2 }).unwrap();
+        thread::sleep(DELAY + DELAY / 2);
+        assert!(q.has_pending_output());
+        assert!(q.has_pending_output());
+        assert_eq!(q.try_recv(), Ok(1));
+        assert!(!q.has_pending_output());
+        assert_eq!(q.recv(), 2);
+
+        // still 2 while the workers are idle; shutdown joins them
+        assert_eq!(q.active_workers(), 2);
+        q.shutdown();
+        assert_eq!(q.active_workers(), 0);
+    }
+
+    #[test]
+    // Test that iter_n stops at n outputs, and stops early instead of hanging when some tasks give none.
+    fn iter_n() {
+        #[derive(Debug)]
+        struct EvenTask(i64);
+        impl Task for EvenTask {
+            type Output = i64;
+            fn run(&self) -> Option<i64> {
+                thread::sleep(DELAY / 10);
+                if self.0 % 2 == 0 {
+                    Some(self.0)
+                } else {
+                    None
+                }
+            }
+        }
+
+        let mut q = WorkQueue::<EvenTask>::new(2);
+        q.enqueue_all((0..6).map(EvenTask)).unwrap();
+        // only 3 of the 6 tasks produce output, so asking for 5 has to end early
+        let mut results: Vec<i64> = q.iter_n(5).collect();
+        results.sort();
+        assert_eq!(results, vec![0, 2, 4]);
+
+        q.enqueue_all((0..10).map(EvenTask)).unwrap();
+        assert_eq!(q.iter_n(2).count(), 2);
+        assert_eq!(q.iter_n(0).count(), 0);
+        assert_eq!(q.iter_n(10).count(), 3);
+    }
+
+    #[test]
+    // Test that par_map gives the Some outputs in input order and survives a panicking input.
+    fn par_map_in_order() {
+        let lengths = par_map(3, vec!["a", "bb", "", "dddd"], |s: &str| Some(s.len()));
+        assert_eq!(lengths, vec![1, 2, 0, 4]);
+
+        let halves = par_map(4, 0..20, |i: u64| {
+            thread::sleep(time::Duration::from_millis(20 - i));
+            if i == 7 {
+                panic!("input 7 is unlucky");
+            }
+            if i.is_multiple_of(2) {
+                Some(i / 2)
+            } else {
+                None
+            }
+        });
+        assert_eq!(halves, (0..10).collect::<Vec<u64>>());
+        assert!(par_map(2, Vec::<u64>::new(), Some).is_empty());
+    }
+
+    #[test]
+    // Test that shutdown lets running tasks finish and skips every queued one, whatever the timing.
+    fn shutdown_skips_queued() {
+        let mut q = WorkQueue::<DelayTask>::new(2);
+        q.enqueue_all((0..6).map(|i| DelayTask { delay: DELAY, result: i })).unwrap();
+        // let both workers start a task
+        thread::sleep(DELAY / 2);
+        let start = Instant::now();
+        q.shutdown();
+        // waited for the two running tasks, not the four queued ones
+        assert!(Instant::now().duration_since(start) < DELAY * 2);
+
+        let mut results: Vec<i64> = q.iter().collect();
+        results.sort();
+        assert_eq!(results, vec![0, 1]);
+        assert_eq!(q.progress_fraction(), 2.0 / 6.0);
+    }
+
+    #[test]
+    // Test that shutdown_timeout skips queued tasks the same way, and detaches workers it can't wait for.
+    fn shutdown_timeout_skips_queued() {
+        let mut q = WorkQueue::<DelayTask>::new(2);
+        q.enqueue_all((0..6).map(|i| DelayTask { delay: DELAY, result: i })).unwrap();
+        thread::sleep(DELAY / 2);
+        q.shutdown_timeout(DELAY * 5);
+        assert_eq!(q.active_workers(), 0);
+        let mut results: Vec<i64> = q.iter().collect();
+        results.sort();
+        assert_eq!(results, vec![0, 1]);
+
+        // not long enough for the running tasks: the workers are detached to finish them, and
+        // their outputs still come through
+        let mut q = WorkQueue::<DelayTask>::new(2);
+        q.enqueue_all((0..6).map(|i| DelayTask { delay: DELAY, result: i })).unwrap();
+        thread::sleep(DELAY / 2);
+        let start = Instant::now();
+        q.shutdown_timeout(Duration::ZERO);
+        assert!(Instant::now().duration_since(start) < DELAY / 4);
+        assert_eq!(q.active_workers(), 0);
+        let mut results: Vec<i64> = q.iter().collect();
+        results.sort();
+        assert_eq!(results, vec![0, 1]);
+
+        // ... and dropping the queue doesn't wait for them either
+        let mut q = WorkQueue::<DelayTask>::new(2);
+        q.enqueue_all((0..6).map(|i| DelayTask { delay: DELAY, result: i })).unwrap();
+        thread::sleep(DELAY / 2);
+        let start = Instant::now();
+        q.shutdown_timeout(Duration::ZERO);
+        q.shutdown();
+        drop(q);
+        assert!(Instant::now().duration_since(start) < DELAY / 4);
+    }
+
+    #[test]
+    // Test that join waits for a whole batch and leaves the pool running for the next one.
+    fn join_between_batches() {
+        let mut q = WorkQueue::<DelayTask>::new(3);
+        q.join(); // nothing enqueued => nothing to wait for
+
+        for batch in 0..3 {
+            let start = Instant::now();
+            q.enqueue_all((0..6).map(|i| DelayTask { delay: DELAY / 4, result: batch * 10 + i }))
+                .unwrap();
+            q.join();
+            // 6 tasks on 3 workers => two rounds
+            assert!(Instant::now().duration_since(start) >= DELAY / 2);
+            assert_eq!(q.current_load(), 0.0);
+
+            let mut results: Vec<i64> = std::iter::from_fn(|| q.try_recv().ok()).collect();
+            results.sort();
+            assert_eq!(results, (0..6).map(|i| batch * 10 + i).collect::<Vec<i64>>());
+        }
+        assert_eq!(q.active_workers(), 3);
+    }
+
+    #[test]
+    // Test that join returns after a shutdown, even with tasks no worker will ever settle.
+    fn join_after_shutdown() {
+        let mut q = WorkQueue::<DelayTask>::new(4);
+        q.enqueue_all((0..200).map(|i| DelayTask { delay: DELAY / 4, result: i })).unwrap();
+        q.shutdown_timeout(5 * DELAY);
+        q.join();
+
+        // no workers => the task just sits in the queue
+        let mut q = WorkQueue::<DelayTask>::new(0);
+        q.enqueue(DelayTask { delay: DELAY, result: 1 }).unwrap();
+        q.shutdown();
+        q.join();
+    }
+
+    #[test]
+    // Test that join doesn't wait on an open queue with no workers running, from the start or after they quit.
+    fn join_without_workers() {
+        let mut q = WorkQueue::<DelayTask>::new(0);
+        q.enqueue(DelayTask { delay: DELAY, result: 1 }).unwrap();
+        q.join();
+
+        // nowhere to send the first output => that worker settles the task and quits
+        let (sink, outputs) = sync::mpsc::channel();
+        drop(outputs);
+        let mut q = WorkQueue::<DelayTask>::new_with_output_sink(1, sink);
+        q.enqueue_all((0..3).map(|i| DelayTask { delay: DELAY / 4, result: i })).unwrap();
+        let start = Instant::now();
+        q.join();
+        assert!(Instant::now().duration_since(start) < DELAY);
+    }
 }